@@ -0,0 +1,371 @@
+use std::{f32, uint, vec};
+use std::rand::{Rng, IsaacRng};
+
+use protocol::Rgb;
+
+/// Where an LED sits, in layout units, for effects that vary across the
+/// piece.
+#[deriving(Clone)]
+pub struct Position {
+    x: f32,
+    y: f32,
+    height: f32,
+}
+
+impl Position {
+    pub fn new(x: f32, y: f32, height: f32) -> Position {
+        Position { x: x, y: y, height: height }
+    }
+}
+
+/// `count` LEDs in a row a unit apart, for when there's no layout to go by.
+pub fn line(count: uint) -> ~[Position] {
+    vec::from_fn(count, |i| Position::new(i as f32, 0.0, 1.0))
+}
+
+/// A light pattern. Effects are run by calling `render` with increasing
+/// times, but needn't be called at any fixed rate.
+pub trait Effect {
+    /// Fills in `colors`, one for each of `layout`, as they should be
+    /// `time` seconds after the effect started.
+    fn render(&mut self, time: float, layout: &[Position], colors: &mut [Rgb]);
+}
+
+/// The names `by_name` knows, in the order they're listed in help.
+pub static NAMES: [&'static str, ..7] = ["flash", "rainbow", "chase", "fade", "twinkle", "breathe", "strobe"];
+
+/// A built-in effect with its default settings.
+pub fn by_name(name: &str) -> Option<~Effect> {
+    let white = (255u8, 255u8, 255u8);
+    match name {
+        "flash" => Some(~RandomFlash::new(0.02) as ~Effect),
+        "rainbow" => Some(~Rainbow::new(5.0) as ~Effect),
+        "chase" => Some(~Chase::new(white, 2.0, 3) as ~Effect),
+        "fade" => Some(~Fade::new(~[(255, 0, 0), (0, 255, 0), (0, 0, 255)], 3.0) as ~Effect),
+        "twinkle" => Some(~Twinkle::new(white, 0.5, 3.0) as ~Effect),
+        "breathe" => Some(~Breathe::new((0, 80, 255), 4.0) as ~Effect),
+        "strobe" => Some(~Strobe::new(white, 10.0, 0.2) as ~Effect),
+        _ => None
+    }
+}
+
+/// `color` at `brightness` from 0 to 1.
+pub fn scale(color: Rgb, brightness: f32) -> Rgb {
+    let (r, g, b) = color;
+    let brightness = brightness.max(&0.0).min(&1.0);
+    (((r as f32) * brightness) as u8, ((g as f32) * brightness) as u8, ((b as f32) * brightness) as u8)
+}
+
+/// Partway from `a` at 0 to `b` at 1.
+pub fn mix(a: Rgb, b: Rgb, t: f32) -> Rgb {
+    let ((ar, ag, ab), (br, bg, bb)) = (a, b);
+    let t = t.max(&0.0).min(&1.0);
+    let lerp = |x: u8, y: u8| ((x as f32) + ((y as f32) - (x as f32)) * t + 0.5) as u8;
+    (lerp(ar, br), lerp(ag, bg), lerp(ab, bb))
+}
+
+/// A fully saturated color at `hue`, in turns from red through green and
+/// blue back to red.
+pub fn hue(hue: f32) -> Rgb {
+    let h = (hue - hue.floor()) * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    let (r, g, b) = match h as uint {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x)
+    };
+    ((r * 255.0 + 0.5) as u8, (g * 255.0 + 0.5) as u8, (b * 255.0 + 0.5) as u8)
+}
+
+/// How far through a cycle of `period` seconds `time` is, from 0 to 1.
+fn phase(time: float, period: float) -> f32 {
+    let cycles = time / period;
+    (cycles - cycles.floor()) as f32
+}
+
+/// Every LED a new random color every `interval` seconds, as
+/// `randomlights.py` did.
+pub struct RandomFlash {
+    interval: float,
+    next: float,
+    rng: IsaacRng,
+}
+
+impl RandomFlash {
+    pub fn new(interval: float) -> RandomFlash {
+        RandomFlash { interval: interval, next: 0.0, rng: IsaacRng::new() }
+    }
+}
+
+impl Effect for RandomFlash {
+    fn render(&mut self, time: float, _: &[Position], colors: &mut [Rgb]) {
+        if time < self.next {
+            return;
+        }
+        self.next = time + self.interval;
+        for colors.mut_iter().advance |color| {
+            *color = (self.rng.gen::<u8>(), self.rng.gen::<u8>(), self.rng.gen::<u8>());
+        }
+    }
+}
+
+/// Hues sweeping across the piece from left to right, each LED going all
+/// the way around every `period` seconds.
+pub struct Rainbow {
+    period: float,
+}
+
+impl Rainbow {
+    pub fn new(period: float) -> Rainbow {
+        Rainbow { period: period }
+    }
+}
+
+impl Effect for Rainbow {
+    fn render(&mut self, time: float, layout: &[Position], colors: &mut [Rgb]) {
+        let mut low = f32::infinity;
+        let mut high = f32::neg_infinity;
+        for layout.iter().advance |p| {
+            low = low.min(&p.x);
+            high = high.max(&p.x);
+        }
+        let width = (high - low).max(&1e-6);
+        let start = phase(time, self.period);
+        for colors.mut_iter().zip(layout.iter()).advance |(color, p)| {
+            *color = hue(start - (p.x - low) / width);
+        }
+    }
+}
+
+/// One LED lit after another in layout order, going all the way along
+/// every `period` seconds, with a tail fading over `tail` more.
+pub struct Chase {
+    color: Rgb,
+    period: float,
+    tail: uint,
+}
+
+impl Chase {
+    pub fn new(color: Rgb, period: float, tail: uint) -> Chase {
+        Chase { color: color, period: period, tail: tail }
+    }
+}
+
+impl Effect for Chase {
+    fn render(&mut self, time: float, _: &[Position], colors: &mut [Rgb]) {
+        let count = colors.len();
+        if count == 0 {
+            return;
+        }
+        let head = phase(time, self.period) * (count as f32);
+        for uint::range(0, count) |i| {
+            // How many places behind the head, wrapping around the end
+            let behind = (head - (i as f32) + (count as f32)) % (count as f32);
+            colors[i] = if behind < 1.0 {
+                self.color
+            } else {
+                scale(self.color, 1.0 - behind / ((self.tail + 1) as f32))
+            };
+        }
+    }
+}
+
+/// Every LED crossfading through `colors` in turn, `period` seconds each.
+pub struct Fade {
+    colors: ~[Rgb],
+    period: float,
+}
+
+impl Fade {
+    pub fn new(colors: ~[Rgb], period: float) -> Fade {
+        Fade { colors: colors, period: period }
+    }
+}
+
+impl Effect for Fade {
+    fn render(&mut self, time: float, _: &[Position], colors: &mut [Rgb]) {
+        if self.colors.is_empty() {
+            return;
+        }
+        let steps = time / self.period;
+        let from = (steps.floor() as uint) % self.colors.len();
+        let to = (from + 1) % self.colors.len();
+        let color = mix(self.colors[from], self.colors[to], (steps - steps.floor()) as f32);
+        for colors.mut_iter().advance |c| {
+            *c = color;
+        }
+    }
+}
+
+/// LEDs lighting up at random, each about `rate` times a second, and
+/// dimming by `decay` (1/e every so many seconds) in between.
+pub struct Twinkle {
+    color: Rgb,
+    rate: f32,
+    decay: f32,
+    brightness: ~[f32],
+    last: float,
+    rng: IsaacRng,
+}
+
+impl Twinkle {
+    pub fn new(color: Rgb, rate: f32, decay: f32) -> Twinkle {
+        Twinkle { color: color, rate: rate, decay: decay, brightness: ~[], last: 0.0, rng: IsaacRng::new() }
+    }
+}
+
+impl Effect for Twinkle {
+    fn render(&mut self, time: float, _: &[Position], colors: &mut [Rgb]) {
+        if self.brightness.len() != colors.len() {
+            self.brightness = vec::from_elem(colors.len(), 0.0f32);
+        }
+        let elapsed = ((time - self.last) as f32).max(&0.0);
+        self.last = time;
+        let fade = (-elapsed * self.decay).exp();
+        for uint::range(0, colors.len()) |i| {
+            self.brightness[i] *= fade;
+            if self.rng.gen::<f32>() < self.rate * elapsed {
+                self.brightness[i] = 1.0;
+            }
+            colors[i] = scale(self.color, self.brightness[i]);
+        }
+    }
+}
+
+/// Every LED slowly brightening and dimming together, once every `period`
+/// seconds.
+pub struct Breathe {
+    color: Rgb,
+    period: float,
+}
+
+impl Breathe {
+    pub fn new(color: Rgb, period: float) -> Breathe {
+        Breathe { color: color, period: period }
+    }
+}
+
+impl Effect for Breathe {
+    fn render(&mut self, time: float, _: &[Position], colors: &mut [Rgb]) {
+        let brightness = (1.0 - (phase(time, self.period) * 2.0 * f32::consts::pi).cos()) / 2.0;
+        let color = scale(self.color, brightness);
+        for colors.mut_iter().advance |c| {
+            *c = color;
+        }
+    }
+}
+
+/// Every LED flashing together `rate` times a second, on for `duty` of
+/// each flash.
+pub struct Strobe {
+    color: Rgb,
+    rate: float,
+    duty: f32,
+}
+
+impl Strobe {
+    pub fn new(color: Rgb, rate: float, duty: f32) -> Strobe {
+        Strobe { color: color, rate: rate, duty: duty }
+    }
+}
+
+impl Effect for Strobe {
+    fn render(&mut self, time: float, _: &[Position], colors: &mut [Rgb]) {
+        let color = if phase(time, 1.0 / self.rate) < self.duty { self.color } else { (0, 0, 0) };
+        for colors.mut_iter().advance |c| {
+            *c = color;
+        }
+    }
+}
+
+#[test]
+fn test_hue() {
+    assert_eq!(hue(0.0), (255, 0, 0));
+    assert_eq!(hue(1.0 / 3.0), (0, 255, 0));
+    assert_eq!(hue(2.0 / 3.0), (0, 0, 255));
+    assert_eq!(hue(-1.0 / 3.0), (0, 0, 255));
+    assert_eq!(mix((0, 0, 0), (255, 100, 10), 0.5), (128, 50, 5));
+}
+
+#[test]
+fn test_chase() {
+    let layout = line(4);
+    let mut colors = vec::from_elem(4, (0u8, 0u8, 0u8));
+    let mut chase = Chase::new((200, 0, 0), 4.0, 1);
+    chase.render(1.0, layout, colors);
+    assert_eq!(colors, ~[(100, 0, 0), (200, 0, 0), (0, 0, 0), (0, 0, 0)]);
+    // The head wraps around to the start, trailing off the end
+    chase.render(4.0, layout, colors);
+    assert_eq!(colors, ~[(200, 0, 0), (0, 0, 0), (0, 0, 0), (100, 0, 0)]);
+}
+
+/// The built-in effect `name`, with any randomness seeded so tests always
+/// see the same frames.
+#[cfg(test)]
+fn seeded(name: &str) -> ~Effect {
+    let white = (255u8, 255u8, 255u8);
+    match name {
+        "flash" => ~RandomFlash { interval: 0.02, next: 0.0, rng: IsaacRng::new_seeded([5, 1]) } as ~Effect,
+        "twinkle" => ~Twinkle { color: white, rate: 0.5, decay: 3.0, brightness: ~[], last: 0.0,
+                                rng: IsaacRng::new_seeded([5, 2]) } as ~Effect,
+        _ => by_name(name).unwrap()
+    }
+}
+
+#[test]
+fn test_every_effect_fills_every_led() {
+    // No effect's defaults can make this: it isn't gray, and has no channel
+    // at 0 or 255
+    let unset = (1u8, 2u8, 3u8);
+    let layout = line(5);
+    for NAMES.iter().advance |&name| {
+        let mut effect = seeded(name);
+        let mut colors = vec::from_elem(5, unset);
+        effect.render(0.5, layout, colors);
+        assert!(colors.iter().all(|&c| c != unset), name);
+    }
+    assert!(by_name("disco").is_none());
+}
+
+#[test]
+fn test_random_effects_change() {
+    let layout = line(20);
+    for ["flash", "twinkle"].iter().advance |&name| {
+        let mut effect = seeded(name);
+        let mut colors = vec::from_elem(20, (0u8, 0u8, 0u8));
+        let mut changes = 0;
+        for uint::range(1, 20) |frame| {
+            let last = colors.clone();
+            effect.render((frame as float) * 0.1, layout, colors);
+            if colors != last {
+                changes += 1;
+            }
+        }
+        assert!(changes > 10, name);
+    }
+}
+
+#[test]
+fn test_on_and_off() {
+    let layout = line(3);
+    let mut colors = vec::from_elem(3, (0u8, 0u8, 0u8));
+
+    // Ten flashes a second, each on for the first fifth
+    let mut strobe = by_name("strobe").unwrap();
+    strobe.render(0.01, layout, colors);
+    assert_eq!(colors, ~[(255, 255, 255), (255, 255, 255), (255, 255, 255)]);
+    strobe.render(0.05, layout, colors);
+    assert_eq!(colors, ~[(0, 0, 0), (0, 0, 0), (0, 0, 0)]);
+
+    // Dark at the start of each four second breath, brightest halfway
+    let mut breathe = by_name("breathe").unwrap();
+    breathe.render(4.0, layout, colors);
+    assert_eq!(colors, ~[(0, 0, 0), (0, 0, 0), (0, 0, 0)]);
+    breathe.render(2.0, layout, colors);
+    for colors.iter().advance |&(r, g, b)| {
+        assert!(r == 0 && g >= 79 && b >= 254);
+    }
+}
@@ -0,0 +1,168 @@
+use std::vec;
+use extra::net::{ip, tcp};
+use extra::uv_global_loop;
+
+use protocol;
+use protocol::Rgb;
+
+/// A connection to the simulator or the sculpture holding a color for each
+/// LED. Colors are only sent on `update`, and then only the ones that
+/// changed.
+pub struct Leds {
+    host: ~str,
+    port: uint,
+    colors: ~[Rgb],
+    /// What the other end was last sent, or `None` if that's unknown, as
+    /// after reconnecting.
+    sent: Option<~[Rgb]>,
+    socket: Option<tcp::TcpSocket>,
+}
+
+impl Leds {
+    /// Connects to `host` and turns all `count` LEDs off.
+    pub fn connect(host: &str, port: uint, count: uint) -> Result<Leds, ~str> {
+        if count > protocol::MAX_LEDS {
+            return Err(fmt!("only %u LEDs can be addressed, not %u", protocol::MAX_LEDS, count));
+        }
+        let mut leds = Leds {
+            host: host.to_owned(),
+            port: port,
+            colors: vec::from_elem(count, (0u8, 0u8, 0u8)),
+            sent: None,
+            socket: None,
+        };
+        match leds.update() {
+            Ok(()) => Ok(leds),
+            Err(error) => Err(error)
+        }
+    }
+
+    pub fn len(&self) -> uint {
+        self.colors.len()
+    }
+
+    pub fn set(&mut self, led: uint, color: Rgb) {
+        self.colors[led] = color;
+    }
+
+    /// Sets the LEDs from `start` on to `colors`, in order.
+    pub fn set_slice(&mut self, start: uint, colors: &[Rgb]) {
+        for colors.iter().enumerate().advance |(i, &color)| {
+            self.colors[start + i] = color;
+        }
+    }
+
+    pub fn fill(&mut self, color: Rgb) {
+        for self.colors.mut_iter().advance |c| {
+            *c = color;
+        }
+    }
+
+    pub fn slice<'a>(&'a self, start: uint, end: uint) -> &'a [Rgb] {
+        self.colors.slice(start, end)
+    }
+
+    pub fn mut_slice<'a>(&'a mut self, start: uint, end: uint) -> &'a mut [Rgb] {
+        self.colors.mut_slice(start, end)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.socket.is_some()
+    }
+
+    /// Sends the colors that changed since the last update. If the
+    /// connection has dropped it's reopened, once, and sent everything.
+    pub fn update(&mut self) -> Result<(), ~str> {
+        match self.try_update() {
+            Ok(()) => Ok(()),
+            Err(_) => {
+                self.disconnect();
+                self.try_update()
+            }
+        }
+    }
+
+    /// Sends every color, whether or not it changed.
+    pub fn force_update(&mut self) -> Result<(), ~str> {
+        self.sent = None;
+        self.update()
+    }
+
+    fn try_update(&mut self) -> Result<(), ~str> {
+        if self.socket.is_none() {
+            match self.open() {
+                Ok(socket) => self.socket = Some(socket),
+                Err(error) => return Err(error)
+            }
+        }
+        for protocol::encode(self.pending()).consume_iter().advance |message| {
+            let written = self.socket.get_ref().write(message);
+            match written {
+                Ok(()) => {},
+                Err(error) => {
+                    self.disconnect();
+                    return Err(fmt!("%s:%u: %s", self.host, self.port, error.err_msg));
+                }
+            }
+        }
+        self.sent = Some(self.colors.clone());
+        Ok(())
+    }
+
+    /// The LEDs the other end doesn't have the current colors for.
+    fn pending(&self) -> ~[(uint, Rgb)] {
+        match self.sent {
+            Some(ref sent) => protocol::diff(*sent, self.colors),
+            None => protocol::all(self.colors)
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.socket = None;
+        self.sent = None;
+    }
+
+    fn open(&self) -> Result<tcp::TcpSocket, ~str> {
+        let iotask = uv_global_loop::get();
+        let mut addrs = match ip::get_addr(self.host, &iotask) {
+            Ok(addrs) => addrs,
+            Err(error) => return Err(fmt!("%s: %?", self.host, error))
+        };
+        if addrs.is_empty() {
+            return Err(fmt!("%s: no addresses", self.host));
+        }
+        match tcp::connect(addrs.shift(), self.port, &iotask) {
+            Ok(socket) => Ok(socket),
+            Err(error) => Err(fmt!("%s:%u: %?", self.host, self.port, error))
+        }
+    }
+}
+
+impl Index<uint, Rgb> for Leds {
+    fn index(&self, led: &uint) -> Rgb {
+        self.colors[*led]
+    }
+}
+
+#[test]
+fn test_pending() {
+    // Never connected, so nothing actually goes anywhere
+    let mut leds = Leds {
+        host: ~"localhost",
+        port: protocol::DEFAULT_PORT,
+        colors: vec::from_elem(4, (0u8, 0u8, 0u8)),
+        sent: None,
+        socket: None,
+    };
+    assert_eq!(leds.pending().len(), 4);
+
+    leds.sent = Some(leds.colors.clone());
+    leds.set(2, (255, 0, 0));
+    leds.set_slice(0, [(0, 0, 0), (0, 9, 0)]);
+    assert_eq!(leds.pending(), ~[(1, (0u8, 9u8, 0u8)), (2, (255u8, 0u8, 0u8))]);
+    assert_eq!(leds[2], (255, 0, 0));
+    assert_eq!(leds.slice(1, 3), &[(0u8, 9u8, 0u8), (255u8, 0u8, 0u8)]);
+
+    leds.disconnect();
+    assert_eq!(leds.pending().len(), 4);
+}
@@ -0,0 +1,11 @@
+#[link(name = "lightsculpture", vers = "0.1")];
+#[crate_type = "lib"];
+
+extern mod extra;
+
+pub use leds::Leds;
+pub use protocol::{Rgb, DEFAULT_PORT};
+
+pub mod protocol;
+pub mod leds;
+pub mod effects;
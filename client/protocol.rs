@@ -0,0 +1,64 @@
+/// The port the simulator and the sculpture listen on.
+pub static DEFAULT_PORT: uint = 7654;
+
+/// LED indices are a single byte.
+pub static MAX_LEDS: uint = 256;
+
+/// So is each message's count of entries.
+pub static MAX_ENTRIES: uint = 255;
+
+pub type Rgb = (u8, u8, u8);
+
+/// Messages setting each LED in `changes` to its color. A message is a count
+/// byte followed by that many `[led, r, g, b]` entries, so long lists of
+/// changes are split across several.
+pub fn encode(changes: &[(uint, Rgb)]) -> ~[~[u8]] {
+    let mut messages = ~[];
+    let mut start = 0;
+    while start < changes.len() {
+        let end = (start + MAX_ENTRIES).min(&changes.len());
+        let mut message = ~[(end - start) as u8];
+        for changes.slice(start, end).iter().advance |&(led, (r, g, b))| {
+            message.push_all([led as u8, r, g, b]);
+        }
+        messages.push(message);
+        start = end;
+    }
+    messages
+}
+
+/// The LEDs whose colors differ between `old` and `new`.
+pub fn diff(old: &[Rgb], new: &[Rgb]) -> ~[(uint, Rgb)] {
+    let mut changes = ~[];
+    for new.iter().enumerate().advance |(led, &color)| {
+        if led >= old.len() || old[led] != color {
+            changes.push((led, color));
+        }
+    }
+    changes
+}
+
+/// Every LED, for when the other end's state is unknown.
+pub fn all(colors: &[Rgb]) -> ~[(uint, Rgb)] {
+    colors.iter().enumerate().transform(|(led, &color)| (led, color)).collect()
+}
+
+#[test]
+fn test_count_matches_entries() {
+    let old = [(0, 0, 0), (1, 2, 3), (0, 0, 0)];
+    let new = [(0, 0, 0), (1, 2, 3), (9, 8, 7)];
+    let messages = encode(diff(old, new));
+    assert_eq!(messages, ~[~[1u8, 2, 9, 8, 7]]);
+    assert!(encode(diff(new, new)).is_empty());
+}
+
+#[test]
+fn test_long_updates_split() {
+    use std::vec;
+    let colors = vec::from_elem(MAX_LEDS, (1u8, 2u8, 3u8));
+    let messages = encode(all(colors));
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0][0] as uint, MAX_ENTRIES);
+    assert_eq!(messages[0].len(), 1 + 4 * MAX_ENTRIES);
+    assert_eq!(messages[1], ~[1u8, 255, 1, 2, 3]);
+}
@@ -0,0 +1,88 @@
+extern mod extra;
+extern mod lightsculpture;
+
+use std::{os, uint, float};
+use extra::getopts::*;
+use extra::{time, timer, uv_global_loop};
+
+use lightsculpture::{Leds, DEFAULT_PORT};
+use lightsculpture::effects;
+
+fn print_usage(program: &str) {
+    printfln!("Usage: %s [options] EFFECT", program);
+    println("");
+    printfln!("EFFECT is one of %s.", effects::NAMES.connect(", "));
+    println("");
+    println("  --host HOST       simulator or sculpture to drive (default localhost)");
+    printfln!("  --port N          port it listens on (default %u)", DEFAULT_PORT);
+    println("  --count N         number of LEDs, laid out in a line (default 14)");
+    println("  --fps N           frames per second to send (default 30)");
+    println("  --duration SECS   how long to run (default: until killed)");
+}
+
+fn parse_positive(matches: &Matches, name: &str, default: uint) -> uint {
+    match opt_maybe_str(matches, name) {
+        Some(value) => match uint::from_str_radix(value, 10) {
+            Some(value) if value > 0 => value,
+            _ => fail!(fmt!("--%s should be a positive whole number", name))
+        },
+        None => default
+    }
+}
+
+fn main() {
+    let args = os::args();
+    let opts = ~[optopt("host"), optopt("port"), optopt("count"), optopt("fps"), optopt("duration"),
+                 optflag("help")];
+    let matches = match getopts(args.tail(), opts) {
+        Ok(matches) => matches,
+        Err(f) => fail!(fail_str(f))
+    };
+    if opt_present(&matches, "help") || matches.free.len() != 1 {
+        print_usage(args[0]);
+        return;
+    }
+
+    let mut effect = match effects::by_name(matches.free[0]) {
+        Some(effect) => effect,
+        None => fail!(fmt!("No effect named \"%s\"; try one of %s", matches.free[0],
+                           effects::NAMES.connect(", ")))
+    };
+    let host = opt_maybe_str(&matches, "host").get_or_default(~"localhost");
+    let port = parse_positive(&matches, "port", DEFAULT_PORT);
+    let count = parse_positive(&matches, "count", 14);
+    let fps = parse_positive(&matches, "fps", 30);
+    let duration = match opt_maybe_str(&matches, "duration") {
+        Some(duration) => match float::from_str(duration) {
+            Some(duration) if duration > 0.0 => Some(duration),
+            _ => fail!("--duration should be a positive number of seconds")
+        },
+        None => None
+    };
+
+    let mut leds = match Leds::connect(host, port, count) {
+        Ok(leds) => leds,
+        Err(error) => fail!(error)
+    };
+    let layout = effects::line(count);
+    let iotask = uv_global_loop::get();
+    let start = time::precise_time_s();
+    loop {
+        let now = time::precise_time_s() - start;
+        match duration {
+            Some(duration) if now >= duration => break,
+            _ => {}
+        }
+        effect.render(now, layout, leds.mut_slice(0, count));
+        match leds.update() {
+            Ok(()) => {},
+            Err(error) => error!(error)
+        }
+        // Sleep off whatever's left of this frame
+        let next = ((now * fps as float).floor() + 1.0) / fps as float;
+        let wait = next - (time::precise_time_s() - start);
+        if wait > 0.0 {
+            timer::sleep(&iotask, (wait * 1000.0) as uint);
+        }
+    }
+}
@@ -19,16 +19,19 @@ class LEDs(object):
 
     def update(self, force=False):
         if force:
-            diff = enumerate(self.colors)
+            diff = list(enumerate(self.colors))
         else:
-            diff = set(enumerate(self.colors)) - set(enumerate(self.old_colors))
-
-        msg = [len(self.colors)]
-        for led, color in diff:
-            msg.append(led)
-            msg.extend(color)
-
-        self.sock.send("".join(chr(i) for i in msg))
+            diff = [(led, color) for led, color in enumerate(self.colors)
+                    if color != self.old_colors[led]]
+
+        # The count is one byte, so big updates go as several messages
+        for start in xrange(0, len(diff), 255):
+            chunk = diff[start:start + 255]
+            msg = [len(chunk)]
+            for led, color in chunk:
+                msg.append(led)
+                msg.extend(color)
+            self.sock.sendall("".join(chr(i) for i in msg))
         self.old_colors = list(self.colors)
 
     def __len__(self):
@@ -0,0 +1,63 @@
+use gl::shader::{ShaderSource, Embedded};
+
+#[cfg(test)]
+use gl::shader::is_input;
+
+/// What every built-in shader uses beyond positions, colors and the model,
+/// view and projection matrices.
+static SURFACE: &'static [&'static str] = &["v_texcoord", "textured", "diffuse_map", "highlight"];
+/// Shading by the surface normal, as well.
+static SHADED: &'static [&'static str] = &["v_texcoord", "textured", "diffuse_map", "highlight",
+                                          "v_normal", "m_inv_transp"];
+/// With specular highlights from the material, as well.
+static LIT: &'static [&'static str] = &["v_texcoord", "textured", "diffuse_map", "highlight",
+                                       "v_normal", "m_inv_transp", "specular", "shininess"];
+
+/// The built-in shaders as (name, vertex source, fragment source, inputs
+/// they must use), named as their files are under `shaders/`.
+pub static SHADERS: [(&'static str, &'static str, &'static str, &'static [&'static str]), ..4] = [
+    ("everything", include_str!("shaders/everything.v.glsl"), include_str!("shaders/everything.f.glsl"), SURFACE),
+    ("lit", include_str!("shaders/lit.v.glsl"), include_str!("shaders/lit.f.glsl"), LIT),
+    ("glow", include_str!("shaders/glow.v.glsl"), include_str!("shaders/glow.f.glsl"), SHADED),
+    ("xray", include_str!("shaders/xray.v.glsl"), include_str!("shaders/xray.f.glsl"), SHADED),
+];
+
+/// The unit-radius, unit-height cylinder rods are cut from.
+pub static ROD_MESH: &'static str = include_str!("cylinder.obj");
+
+/// The layout shown when none is given.
+pub static DEFAULT_RODS: &'static str = include_str!("default-rods.json");
+
+/// The built-in shader called `name`, if there is one.
+pub fn shader(name: &str) -> Option<ShaderSource> {
+    for SHADERS.iter().advance |&(shader_name, vertex, fragment, required)| {
+        if shader_name == name {
+            return Some(Embedded(shader_name, vertex, fragment, required));
+        }
+    }
+    None
+}
+
+/// The inputs the built-in shader called `name` must use, which copies of
+/// it loaded from files are held to as well.
+pub fn required_inputs(name: &str) -> Option<&'static [&'static str]> {
+    match shader(name) {
+        Some(source) => Some(source.required()),
+        None => None
+    }
+}
+
+#[test]
+fn test_required_inputs() {
+    for SHADERS.iter().advance |&(name, _, _, required)| {
+        for required.iter().advance |&input| {
+            assert!(is_input(input), fmt!("%s requires unknown input %s", name, input));
+        }
+    }
+    // Anything shaded by its normals has to actually get them
+    for ["lit", "glow", "xray"].iter().advance |&name| {
+        let required = required_inputs(name).unwrap();
+        assert!(required.contains(&"v_normal") && required.contains(&"m_inv_transp"));
+    }
+    assert!(required_inputs("lit").unwrap().contains(&"shininess"));
+}
@@ -0,0 +1,341 @@
+use std::{os, uint};
+use std::hashmap::HashMap;
+use extra::json;
+
+use math::Quat;
+use gl::camera::Pose;
+use config;
+
+pub struct Bookmark {
+    name: ~str,
+    slot: Option<uint>,
+    pose: Pose,
+}
+
+pub struct Keyframe {
+    bookmark: ~str,
+    time: f32,
+}
+
+pub struct CameraPath {
+    name: ~str,
+    keyframes: ~[Keyframe],
+}
+
+/// Named camera views and the flythrough paths between them, as stored in a
+/// JSON file. `orientation` is a quaternion (x, y, z, w); files from before
+/// it only have `rotation`, Euler angles about x, y and z, which are still
+/// written alongside it:
+///
+///     {"bookmarks": [{"name": "top", "slot": 2, "translation": [0, 0, 0],
+///                     "orientation": [0.7071, 0, 0, 0.7071], "rotation": [1.57, 0, 0]}],
+///      "paths": [{"name": "intro",
+///                 "keyframes": [{"bookmark": "top", "time": 0}, ...]}]}
+pub struct Bookmarks {
+    file: ~str,
+    bookmarks: ~[Bookmark],
+    paths: ~[CameraPath],
+}
+
+impl Bookmarks {
+    pub fn load(file: &str) -> Result<Bookmarks, ~str> {
+        let mut result = Bookmarks { file: file.to_owned(), bookmarks: ~[], paths: ~[] };
+        if !os::path_exists(&Path(file)) {
+            return Ok(result);
+        }
+
+        let obj = match config::read_json_file(file) {
+            Ok(json::Object(obj)) => obj,
+            Ok(_) => return Err(fmt!("%s: expected an object", file)),
+            Err(error) => return Err(error)
+        };
+
+        match obj.find_equiv(&("bookmarks")) {
+            Some(&json::List(ref list)) => {
+                for list.iter().advance |json| {
+                    match parse_bookmark(json) {
+                        Ok(bookmark) => result.bookmarks.push(bookmark),
+                        Err(error) => return Err(fmt!("%s: bookmark: %s", file, error))
+                    }
+                }
+            },
+            Some(_) => return Err(fmt!("%s: \"bookmarks\" should be a list", file)),
+            None => {}
+        }
+
+        match obj.find_equiv(&("paths")) {
+            Some(&json::List(ref list)) => {
+                for list.iter().advance |json| {
+                    match parse_path(json) {
+                        Ok(path) => result.paths.push(path),
+                        Err(error) => return Err(fmt!("%s: path: %s", file, error))
+                    }
+                }
+            },
+            Some(_) => return Err(fmt!("%s: \"paths\" should be a list", file)),
+            None => {}
+        }
+
+        Ok(result)
+    }
+
+    pub fn save(&self) -> Result<(), ~str> {
+        config::write_json_file(self.file, &self.to_json())
+    }
+
+    fn to_json(&self) -> json::Json {
+        let bookmarks = do self.bookmarks.map |bookmark| {
+            let mut obj = ~HashMap::new();
+            obj.insert(~"name", json::String(bookmark.name.clone()));
+            match bookmark.slot {
+                Some(slot) => { obj.insert(~"slot", json::Number(slot as float)); },
+                None => {}
+            }
+            obj.insert(~"translation", config::vec3_to_json(&bookmark.pose.translation));
+            let q = &bookmark.pose.orientation;
+            obj.insert(~"orientation", json::List(~[json::Number(q.x as float), json::Number(q.y as float),
+                                                   json::Number(q.z as float), json::Number(q.w as float)]));
+            obj.insert(~"rotation", config::vec3_to_json(&q.to_euler()));
+            json::Object(obj)
+        };
+        let paths = do self.paths.map |path| {
+            let keyframes = do path.keyframes.map |keyframe| {
+                let mut obj = ~HashMap::new();
+                obj.insert(~"bookmark", json::String(keyframe.bookmark.clone()));
+                obj.insert(~"time", json::Number(keyframe.time as float));
+                json::Object(obj)
+            };
+            let mut obj = ~HashMap::new();
+            obj.insert(~"name", json::String(path.name.clone()));
+            obj.insert(~"keyframes", json::List(keyframes));
+            json::Object(obj)
+        };
+
+        let mut obj = ~HashMap::new();
+        obj.insert(~"bookmarks", json::List(bookmarks));
+        obj.insert(~"paths", json::List(paths));
+        json::Object(obj)
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Bookmark> {
+        self.bookmarks.iter().find_(|bookmark| bookmark.name.as_slice() == name)
+    }
+
+    pub fn find_slot(&self, slot: uint) -> Option<&Bookmark> {
+        self.bookmarks.iter().find_(|bookmark| bookmark.slot == Some(slot))
+    }
+
+    /// Stores `pose` in a numbered slot, keeping the name of whatever
+    /// bookmark already had that slot.
+    pub fn set_slot(&mut self, slot: uint, pose: Pose) {
+        for self.bookmarks.mut_iter().advance |bookmark| {
+            if bookmark.slot == Some(slot) {
+                bookmark.pose = pose;
+                return;
+            }
+        }
+        self.bookmarks.push(Bookmark { name: fmt!("view %u", slot), slot: Some(slot), pose: pose });
+    }
+
+    pub fn find_path(&self, name: &str) -> Option<&CameraPath> {
+        self.paths.iter().find_(|path| path.name.as_slice() == name)
+    }
+}
+
+fn parse_bookmark(json: &json::Json) -> Result<Bookmark, ~str> {
+    let obj = match *json {
+        json::Object(ref obj) => obj,
+        _ => return Err(~"expected an object")
+    };
+    let name = match config::find_string(&**obj, "name") { Ok(name) => name, Err(error) => return Err(error) };
+    let slot = match obj.find_equiv(&("slot")) {
+        Some(&json::Number(num)) if num >= 1.0 && num <= 9.0 => Some(num as uint),
+        Some(_) => return Err(fmt!("%s: \"slot\" should be a number from 1 to 9", name)),
+        None => None
+    };
+    let translation = match config::find_vec3(&**obj, "translation") {
+        Ok(v) => v,
+        Err(error) => return Err(fmt!("%s: %s", name, error))
+    };
+    let orientation = match obj.find_equiv(&("orientation")) {
+        Some(&json::List(ref list)) if list.len() == 4 => match (&list[0], &list[1], &list[2], &list[3]) {
+            (&json::Number(x), &json::Number(y), &json::Number(z), &json::Number(w)) =>
+                Quat::new(x as f32, y as f32, z as f32, w as f32).normalize(),
+            _ => return Err(fmt!("%s: \"orientation\" should be a list of four numbers", name))
+        },
+        Some(_) => return Err(fmt!("%s: \"orientation\" should be a list of four numbers", name)),
+        None => match config::find_vec3(&**obj, "rotation") {
+            Ok(v) => Quat::from_euler(v.x, v.y, v.z),
+            Err(error) => return Err(fmt!("%s: %s", name, error))
+        }
+    };
+    Ok(Bookmark { name: name, slot: slot, pose: Pose { translation: translation, orientation: orientation } })
+}
+
+fn parse_path(json: &json::Json) -> Result<CameraPath, ~str> {
+    let obj = match *json {
+        json::Object(ref obj) => obj,
+        _ => return Err(~"expected an object")
+    };
+    let name = match config::find_string(&**obj, "name") { Ok(name) => name, Err(error) => return Err(error) };
+    let mut keyframes: ~[Keyframe] = ~[];
+    match obj.find_equiv(&("keyframes")) {
+        Some(&json::List(ref list)) => {
+            for list.iter().advance |json| {
+                let keyframe = match *json {
+                    json::Object(ref obj) => {
+                        match (config::find_string(&**obj, "bookmark"), config::find_number(&**obj, "time")) {
+                            (Ok(bookmark), Ok(time)) => Keyframe { bookmark: bookmark, time: time as f32 },
+                            (Err(error), _) | (_, Err(error)) => return Err(fmt!("%s: %s", name, error))
+                        }
+                    },
+                    _ => return Err(fmt!("%s: keyframes should be objects", name))
+                };
+                if keyframes.len() > 0 && keyframe.time <= keyframes[keyframes.len() - 1].time {
+                    return Err(fmt!("%s: keyframe times must increase", name));
+                }
+                keyframes.push(keyframe);
+            }
+        },
+        _ => return Err(fmt!("%s: \"keyframes\" should be a list", name))
+    }
+    Ok(CameraPath { name: name, keyframes: keyframes })
+}
+
+/// A camera path with its bookmarks resolved, ready to be sampled.
+pub struct Flythrough {
+    times: ~[f32],
+    poses: ~[Pose],
+}
+
+impl Flythrough {
+    pub fn new(path: &CameraPath, bookmarks: &Bookmarks) -> Result<Flythrough, ~str> {
+        if path.keyframes.len() == 0 {
+            return Err(fmt!("%s: path has no keyframes", path.name));
+        }
+        let mut times = ~[];
+        let mut poses = ~[];
+        for path.keyframes.iter().advance |keyframe| {
+            match bookmarks.find(keyframe.bookmark) {
+                Some(bookmark) => {
+                    times.push(keyframe.time);
+                    poses.push(bookmark.pose.clone());
+                },
+                None => return Err(fmt!("%s: no bookmark named \"%s\"", path.name, keyframe.bookmark))
+            }
+        }
+        Ok(Flythrough { times: times, poses: poses })
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.times[self.times.len() - 1]
+    }
+
+    /// Samples the path at `time` seconds with a Catmull-Rom spline through
+    /// the keyframes, so the camera passes through every bookmark without
+    /// stopping at it. Returns `None` once the path is finished.
+    pub fn pose_at(&self, time: f32) -> Option<Pose> {
+        let n = self.times.len();
+        if time > self.duration() {
+            return None;
+        }
+        if n == 1 || time <= self.times[0] {
+            return Some(self.poses[0].clone());
+        }
+
+        let mut i = 0;
+        while self.times[i + 1] < time {
+            i += 1;
+        }
+        let u = (time - self.times[i]) / (self.times[i + 1] - self.times[i]);
+        let (u2, u3) = (u * u, u * u * u);
+        let weights = [-0.5 * u + u2 - 0.5 * u3,
+                       1.0 - 2.5 * u2 + 1.5 * u3,
+                       0.5 * u + 2.0 * u2 - 1.5 * u3,
+                       -0.5 * u2 + 0.5 * u3];
+        let p0 = &self.poses[if i == 0 { 0 } else { i - 1 }];
+        let p1 = &self.poses[i];
+        let p2 = &self.poses[i + 1];
+        let p3 = &self.poses[if i + 2 < n { i + 2 } else { n - 1 }];
+
+        Some(Pose {
+            translation: p0.translation * weights[0] + p1.translation * weights[1] +
+                         p2.translation * weights[2] + p3.translation * weights[3],
+            orientation: blend([&p0.orientation, &p1.orientation, &p2.orientation, &p3.orientation], weights),
+        })
+    }
+}
+
+/// Weighs quaternions together, turning each to the same side as the
+/// second, the one being left. Close to what splining the angles would give
+/// for nearby keyframes, without wrapping around at a full turn.
+fn blend(quats: [&Quat, ..4], weights: [f32, ..4]) -> Quat {
+    let mut sum = Quat::new(0.0, 0.0, 0.0, 0.0);
+    for uint::range(0, 4) |i| {
+        let q = quats[i];
+        let weight = if q.dot(quats[1]) < 0.0 { -weights[i] } else { weights[i] };
+        sum = Quat::new(sum.x + q.x * weight, sum.y + q.y * weight, sum.z + q.z * weight, sum.w + q.w * weight);
+    }
+    sum.normalize()
+}
+
+#[cfg(test)]
+fn same_rotation(a: &Quat, b: &Quat) -> bool {
+    (a.dot(b).abs() - 1.0).abs() < 1e-5
+}
+
+#[test]
+fn test_old_rotation() {
+    let text = "{\"name\": \"old\", \"translation\": [1, 2, 3], \"rotation\": [0.5, -0.25, 1.0]}";
+    let bookmark = match parse_bookmark(&config::read_json_str(text, "old").unwrap()) {
+        Ok(bookmark) => bookmark,
+        Err(error) => fail!(error)
+    };
+    assert!(same_rotation(&bookmark.pose.orientation, &Quat::from_euler(0.5, -0.25, 1.0)));
+    let t = &bookmark.pose.translation;
+    assert!(t.x == 1.0 && t.y == 2.0 && t.z == 3.0);
+}
+
+#[test]
+fn test_orientation_round_trip() {
+    use math::Vec3;
+    let orientation = Quat::from_axis_angle(Vec3::new(0.3f32, -1.0, 0.6), 2.5);
+    let mut bookmarks = Bookmarks { file: ~"unused.json", bookmarks: ~[], paths: ~[] };
+    bookmarks.set_slot(4, Pose { translation: Vec3::new(1.0f32, 0.5, -2.0), orientation: orientation });
+    let json = bookmarks.to_json();
+    let saved = match json {
+        json::Object(ref obj) => match obj.find_equiv(&("bookmarks")) {
+            Some(&json::List(ref list)) => list[0].clone(),
+            _ => fail!("no bookmarks saved")
+        },
+        _ => fail!("saved something other than an object")
+    };
+    let bookmark = match parse_bookmark(&saved) {
+        Ok(bookmark) => bookmark,
+        Err(error) => fail!(error)
+    };
+    assert_eq!(bookmark.slot, Some(4));
+    let q = &bookmark.pose.orientation;
+    assert!((q.x - orientation.x).abs() < 1e-5 && (q.y - orientation.y).abs() < 1e-5 &&
+            (q.z - orientation.z).abs() < 1e-5 && (q.w - orientation.w).abs() < 1e-5);
+}
+
+#[test]
+fn test_blend() {
+    use math::Vec3;
+    let axis = Vec3::new(0.0f32, 1.0, 0.0);
+    let a = Quat::from_axis_angle(axis.clone(), 0.2);
+    let b = Quat::from_axis_angle(axis.clone(), 0.6);
+    // Halfway, as pose_at weighs them
+    let weights = [-0.0625f32, 0.5625, 0.5625, -0.0625];
+
+    let same = blend([&a, &a, &a, &a], weights);
+    assert!((same.x - a.x).abs() < 1e-6 && (same.y - a.y).abs() < 1e-6 &&
+            (same.z - a.z).abs() < 1e-6 && (same.w - a.w).abs() < 1e-6);
+
+    let expected = blend([&a, &a, &b, &b], weights);
+    assert!(same_rotation(&expected, &Quat::from_axis_angle(axis.clone(), 0.4)));
+    let (neg_a, neg_b) = (Quat::new(-a.x, -a.y, -a.z, -a.w), Quat::new(-b.x, -b.y, -b.z, -b.w));
+    assert!(same_rotation(&blend([&neg_a, &a, &neg_b, &b], weights), &expected));
+    assert!(same_rotation(&blend([&a, &neg_a, &b, &neg_b], weights), &expected));
+}
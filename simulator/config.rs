@@ -0,0 +1,66 @@
+use std::io;
+use extra::json;
+
+use math::Vec3;
+
+pub fn read_json_file(path_str: &str) -> Result<json::Json, ~str> {
+    let reader = match io::file_reader(&Path(path_str)) {
+        Ok(reader) => reader,
+        Err(error) => return Err(error)
+    };
+    match json::from_reader(reader) {
+        Ok(json) => Ok(json),
+        Err(error) => Err(fmt!("%s:%u:%u: %s", path_str, error.line, error.col, *error.msg))
+    }
+}
+
+/// Parses JSON already in memory, naming it `source` in errors.
+pub fn read_json_str(text: &str, source: &str) -> Result<json::Json, ~str> {
+    match json::from_str(text) {
+        Ok(json) => Ok(json),
+        Err(error) => Err(fmt!("%s:%u:%u: %s", source, error.line, error.col, *error.msg))
+    }
+}
+
+pub fn write_json_file(path_str: &str, json: &json::Json) -> Result<(), ~str> {
+    let writer = match io::file_writer(&Path(path_str), [io::Create, io::Truncate]) {
+        Ok(writer) => writer,
+        Err(error) => return Err(error)
+    };
+    writer.write_str(json.to_pretty_str());
+    writer.write_char('\n');
+    Ok(())
+}
+
+pub fn find_number(obj: &json::Object, key: &str) -> Result<float, ~str> {
+    match obj.find_equiv(&key) {
+        Some(&json::Number(num)) => Ok(num),
+        Some(_) => Err(fmt!("\"%s\" should be a number", key)),
+        None => Err(fmt!("missing \"%s\"", key))
+    }
+}
+
+pub fn find_string(obj: &json::Object, key: &str) -> Result<~str, ~str> {
+    match obj.find_equiv(&key) {
+        Some(&json::String(ref string)) => Ok(string.clone()),
+        Some(_) => Err(fmt!("\"%s\" should be a string", key)),
+        None => Err(fmt!("missing \"%s\"", key))
+    }
+}
+
+pub fn find_vec3(obj: &json::Object, key: &str) -> Result<Vec3<f32>, ~str> {
+    match obj.find_equiv(&key) {
+        Some(&json::List(ref list)) if list.len() == 3 => {
+            match (&list[0], &list[1], &list[2]) {
+                (&json::Number(x), &json::Number(y), &json::Number(z)) => Ok(Vec3::new(x as f32, y as f32, z as f32)),
+                _ => Err(fmt!("\"%s\" should be a list of three numbers", key))
+            }
+        },
+        Some(_) => Err(fmt!("\"%s\" should be a list of three numbers", key)),
+        None => Err(fmt!("missing \"%s\"", key))
+    }
+}
+
+pub fn vec3_to_json(v: &Vec3<f32>) -> json::Json {
+    json::List(~[json::Number(v.x as float), json::Number(v.y as float), json::Number(v.z as float)])
+}
@@ -0,0 +1,124 @@
+use std::io;
+
+use math::Vec3;
+use gl::obj::Mesh;
+
+#[cfg(test)]
+use gl::obj::test_mesh;
+
+/// Writes `meshes` to one file, scaling scene units by `scale`. Files
+/// ending in `.stl` are ASCII STL with a solid per mesh; anything else is
+/// OBJ with an object per mesh. Axes stay as in the scene, with y up.
+pub fn export(path_str: &str, meshes: &[Mesh], scale: f32) -> Result<(), ~str> {
+    let writer = match io::file_writer(&Path(path_str), [io::Create, io::Truncate]) {
+        Ok(writer) => writer,
+        Err(error) => return Err(fmt!("%s: %s", path_str, error))
+    };
+    if path_str.ends_with(".stl") || path_str.ends_with(".STL") {
+        write_stl(writer, meshes, scale);
+    } else {
+        write_obj(writer, meshes, scale);
+    }
+    Ok(())
+}
+
+/// The mesh's vertices where they are in the scene, scaled.
+fn placed_vertices(mesh: &Mesh, scale: f32) -> ~[Vec3<f32>] {
+    do mesh.vertices.map |v| {
+        let placed = mesh.object2world.transform(v);
+        Vec3::new(placed.x * scale, placed.y * scale, placed.z * scale)
+    }
+}
+
+fn mesh_name(mesh: &Mesh, index: uint) -> ~str {
+    match mesh.name {
+        Some(ref name) => name.clone(),
+        None => fmt!("mesh %u", index)
+    }
+}
+
+pub fn write_obj(writer: @Writer, meshes: &[Mesh], scale: f32) {
+    writer.write_line("# Exported from the light sculpture simulator");
+    // Indices run on across objects, counting from 1
+    let (mut vertex_offset, mut normal_offset) = (1u, 1u);
+    for meshes.iter().enumerate().advance |(i, mesh)| {
+        writer.write_line(fmt!("o %s", mesh_name(mesh, i)));
+        let vertices = placed_vertices(mesh, scale);
+        for vertices.iter().advance |v| {
+            writer.write_line(fmt!("v %f %f %f", v.x as float, v.y as float, v.z as float));
+        }
+        let has_normals = mesh.normals.len() == vertices.len();
+        if has_normals {
+            for mesh.normals.iter().advance |n| {
+                writer.write_line(fmt!("vn %f %f %f", n.x as float, n.y as float, n.z as float));
+            }
+        }
+        let mut j = 0;
+        while j + 2 < mesh.elements.len() {
+            let corner = |k: uint| {
+                let index = mesh.elements[j + k] as uint;
+                if has_normals { fmt!("%u//%u", index + vertex_offset, index + normal_offset) }
+                else { fmt!("%u", index + vertex_offset) }
+            };
+            writer.write_line(fmt!("f %s %s %s", corner(0), corner(1), corner(2)));
+            j += 3;
+        }
+        vertex_offset += vertices.len();
+        if has_normals {
+            normal_offset += vertices.len();
+        }
+    }
+}
+
+pub fn write_stl(writer: @Writer, meshes: &[Mesh], scale: f32) {
+    for meshes.iter().enumerate().advance |(i, mesh)| {
+        let name = mesh_name(mesh, i);
+        writer.write_line(fmt!("solid %s", name));
+        let vertices = placed_vertices(mesh, scale);
+        let mut j = 0;
+        while j + 2 < mesh.elements.len() {
+            let (a, b, c) = (&vertices[mesh.elements[j] as uint], &vertices[mesh.elements[j + 1] as uint],
+                             &vertices[mesh.elements[j + 2] as uint]);
+            let cross = (*b - *a).cross(&(*c - *a));
+            // Degenerate slivers get a zero normal, which readers accept
+            let n = if cross.dot(&cross) > 0.0 { cross.normalize() } else { cross };
+            writer.write_line(fmt!("  facet normal %f %f %f", n.x as float, n.y as float, n.z as float));
+            writer.write_line("    outer loop");
+            for [a, b, c].iter().advance |&v| {
+                writer.write_line(fmt!("      vertex %f %f %f", v.x as float, v.y as float, v.z as float));
+            }
+            writer.write_line("    endloop");
+            writer.write_line("  endfacet");
+            j += 3;
+        }
+        writer.write_line(fmt!("endsolid %s", name));
+    }
+}
+
+#[cfg(test)]
+fn test_triangle() -> Mesh {
+    let mut mesh = test_mesh([(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0)], ~[0, 1, 2]);
+    mesh.name = Some(~"tri");
+    mesh
+}
+
+#[test]
+fn test_obj_offsets() {
+    let meshes = [test_triangle(), test_triangle()];
+    let text = do io::with_str_writer |writer| { write_obj(writer, meshes, 10.0) };
+    let lines: ~[&str] = text.line_iter().collect();
+    assert!(lines.contains(&"o tri"));
+    assert!(lines.contains(&"v 10.000000 0.000000 0.000000"));
+    assert!(lines.contains(&"f 1//1 2//2 3//3"));
+    assert!(lines.contains(&"f 4//4 5//5 6//6"));
+}
+
+#[test]
+fn test_stl_facets() {
+    let meshes = [test_triangle()];
+    let text = do io::with_str_writer |writer| { write_stl(writer, meshes, 1.0) };
+    let lines: ~[&str] = text.line_iter().collect();
+    assert_eq!(lines[0], "solid tri");
+    assert_eq!(lines[1], "  facet normal 0.000000 0.000000 1.000000");
+    assert_eq!(lines[lines.len() - 1], "endsolid tri");
+}
@@ -3,63 +3,165 @@ use glfw;
 
 use std::uint;
 use std::libc::c_int;
-use std::hashmap::HashMap;
+use std::hashmap::HashSet;
+use extra::sort;
+use extra::time;
 
-use math::{Vec3, Mat4};
+use math::{Vec3, Vec4, Mat4, Quat, Ray, Aabb};
 use gl::Mesh;
-use gl::shader::{Shader, AttribLocation, UniformLocation};
+use gl::shader::{Program, ShaderError, ShaderSource};
 
 use glfw::Window;
 
+use es = opengles::gl2;
+
+#[macro_escape]
+#[path = "../check.rs"]
+mod check;
+
+/// The user-controlled part of the camera, as moved by the keyboard and
+/// saved in bookmarks.
+#[deriving(Clone)]
+pub struct Pose {
+    translation: Vec3<f32>,
+    orientation: Quat,
+}
+
+impl Pose {
+    /// Turns the scene by the orientation, then moves it by the translation.
+    pub fn calc_model(&self) -> Mat4<f32> {
+        Mat4::ident().translate(self.translation) * self.orientation.to_mat4()
+    }
+}
+
+/// The kinds of view the camera can draw.
+#[deriving(Eq, Clone)]
+pub enum View {
+    /// Through the user's camera, moved with the keyboard.
+    Perspective,
+    /// Orthographic, straight down onto the floor.
+    Plan,
+    /// Orthographic, at the front of the piece.
+    Elevation,
+}
+
+impl View {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Perspective => "perspective",
+            Plan => "plan",
+            Elevation => "elevation",
+        }
+    }
+}
+
+/// How the window is shared between views.
+#[deriving(Eq, Clone)]
+pub enum ViewLayout {
+    Single(View),
+    /// Perspective on the left, with plan above elevation on the right.
+    Split,
+}
+
+impl ViewLayout {
+    /// The layout after this one when cycling through them all.
+    pub fn next(&self) -> ViewLayout {
+        match *self {
+            Single(Perspective) => Single(Plan),
+            Single(Plan) => Single(Elevation),
+            Single(Elevation) => Split,
+            Split => Single(Perspective),
+        }
+    }
+    
+    pub fn from_name(name: &str) -> Option<ViewLayout> {
+        match name {
+            "perspective" => Some(Single(Perspective)),
+            "plan" => Some(Single(Plan)),
+            "elevation" => Some(Single(Elevation)),
+            "split" => Some(Split),
+            _ => None
+        }
+    }
+}
+
 pub struct Camera {
     window: ~Window,
+    width: int,
+    height: int,
+    covering: bool,
+    windowed_rect: (int, int, int, int),
+    keys_held: HashSet<c_int>,
+    mouse_held: bool,
     translation: Vec3<f32>,
-    rotation: Vec3<f32>,
+    orientation: Quat,
     eye: Vec3<f32>,
     center: Vec3<f32>,
     up: Vec3<f32>,
     fovy: f32,
-    aspect: f32,
     z_near: f32,
     z_far: f32,
-    program: ~Shader,
-    attribs: ~HashMap<~str, AttribLocation>,
-    uniforms: ~HashMap<~str, UniformLocation>,
+    layout: ViewLayout,
+    /// Corners of the box around every mesh, which orthographic views frame.
+    bounds: Option<Aabb>,
+    program: Program,
+    overlay_program: Program,
+    last_reload_check: float,
     meshes: ~[Mesh],
 }
 
+/// How often shader sources are checked for edits, in seconds.
+static RELOAD_INTERVAL: float = 1.0;
+
+/// Room left around the scene in orthographic views.
+static ORTHO_MARGIN: f32 = 1.1;
+
+/// The perspective view's field of view and near and far planes until
+/// `perspective` changes them.
+static START_FOVY: f32 = 3.14159 / 4.0;
+static START_Z_NEAR: f32 = 0.1;
+static START_Z_FAR: f32 = 10.0;
+
+/// Where the perspective view looks from and toward, and which way is up,
+/// until `look_at` moves it.
+fn start_view() -> (Vec3<f32>, Vec3<f32>, Vec3<f32>) {
+    (Vec3::new(0.0f32, 1.0, 0.0), Vec3::new(0.0f32, -2.0, -2.0), Vec3::new(0.0f32, 0.0, 1.0))
+}
+
 impl Camera {
-    pub fn new(window: ~Window, shader_name: &str) -> Camera {
-        let (width, height) = window.get_size();
-        let program = Shader::from_files(fmt!("%s.v.glsl", shader_name), fmt!("%s.f.glsl", shader_name));
+    pub fn new(window: ~Window, shader: &ShaderSource) -> Camera {
+        let (width, height) = window.get_framebuffer_size();
+        // The overlay keeps the starting shader whatever the scene switches to
+        let (program, overlay_program) = match (Program::load(shader), Program::load(shader)) {
+            (Ok(program), Ok(overlay_program)) => (program, overlay_program),
+            (Err(error), _) | (_, Err(error)) => fail!(error.to_str())
+        };
         let zero_vec = Vec3::new(0.0f32, 0.0, 0.0);
+        let (eye, center, up) = start_view();
         
-        let mut attribs = HashMap::new();
-        attribs.insert(~"v_coord", program.get_attrib_location("v_coord"));
-        attribs.insert(~"v_normal", program.get_attrib_location("v_normal"));
-        attribs.insert(~"v_color", program.get_attrib_location("v_color"));
-        
-        let mut uniforms = HashMap::new();
-        uniforms.insert(~"m_orig", program.get_uniform_location("m_orig"));
-        uniforms.insert(~"m", program.get_uniform_location("m"));
-        uniforms.insert(~"v", program.get_uniform_location("v"));
-        uniforms.insert(~"p", program.get_uniform_location("p"));
-        uniforms.insert(~"m_inv_transp", program.get_uniform_location("m_inv_transp"));
+        check!(es::viewport(0, 0, width as es::GLsizei, height as es::GLsizei));
         
         Camera {
             window: window,
+            width: width,
+            height: height,
+            covering: false,
+            windowed_rect: (0, 0, width, height),
+            keys_held: HashSet::new(),
+            mouse_held: false,
             translation: zero_vec.clone(),
-            rotation: zero_vec.clone(),
-            eye: zero_vec.clone(),
-            center: zero_vec.clone(),
-            up: zero_vec.clone(),
-            fovy: 0.0,
-            aspect: (width as f32) / (height as f32),
-            z_near: 0.0,
-            z_far: 0.0,
-            program: ~program,
-            attribs: ~attribs,
-            uniforms: ~uniforms,
+            orientation: Quat::ident(),
+            eye: eye,
+            center: center,
+            up: up,
+            fovy: START_FOVY,
+            z_near: START_Z_NEAR,
+            z_far: START_Z_FAR,
+            layout: Single(Perspective),
+            bounds: None,
+            program: program,
+            overlay_program: overlay_program,
+            last_reload_check: time::precise_time_s(),
             meshes: ~[],
         }
     }
@@ -68,16 +170,61 @@ impl Camera {
         self.translation = self.translation + translation;
     }
     
+    /// Tilts the scene by `x` radians about the view's x axis, and turns it
+    /// by `y` and `z` about its own y and z axes. Unlike adding up Euler
+    /// angles, turns stay about the same axes however far it's tilted.
     pub fn rotate(&mut self, x: f32, y: f32, z: f32) {
-        self.rotation = self.rotation + Vec3::new(x, y, z);
+        let tilt = Quat::from_axis_angle(Vec3::new(1.0f32, 0.0, 0.0), x);
+        let turn = Quat::from_axis_angle(Vec3::new(0.0f32, 1.0, 0.0), y) *
+                   Quat::from_axis_angle(Vec3::new(0.0f32, 0.0, 1.0), z);
+        self.orientation = (tilt * self.orientation * turn).normalize();
+    }
+    
+    pub fn pose(&self) -> Pose {
+        Pose { translation: self.translation.clone(), orientation: self.orientation.clone() }
+    }
+    
+    pub fn set_pose(&mut self, pose: &Pose) {
+        self.translation = pose.translation.clone();
+        self.orientation = pose.orientation.clone();
     }
     
     pub fn calc_model(&self) -> Mat4<f32> {
-        let mut mat = Mat4::ident().translate(self.translation);
-        mat = mat.rotate(self.rotation.x, Vec3::new(1.0, 0.0, 0.0));
-        mat = mat.rotate(self.rotation.y, Vec3::new(0.0, 1.0, 0.0));
-        mat = mat.rotate(self.rotation.z, Vec3::new(0.0, 0.0, 1.0));
-        mat
+        self.pose().calc_model()
+    }
+    
+    /// The ray through a point in the window, in window coordinates as the
+    /// cursor reports them, in scene coordinates.
+    /// The point is looked up in whichever view it falls in.
+    pub fn pick_ray(&self, x: float, y: float) -> Ray {
+        let ratio = self.pixel_ratio();
+        let (px, py) = ((x as f32) * ratio, (y as f32) * ratio);
+        let panes = self.panes();
+        let mut pane = panes[0];
+        for panes.iter().advance |&(view, (left, top, width, height))| {
+            if px >= left as f32 && px < (left + width) as f32 && py >= top as f32 && py < (top + height) as f32 {
+                pane = (view, (left, top, width, height));
+            }
+        }
+        let (view, (left, top, width, height)) = pane;
+        let ndc_x = 2.0 * (px - left as f32) / (width as f32) - 1.0;
+        let ndc_y = 1.0 - 2.0 * (py - top as f32) / (height as f32);
+        let aspect = (width as f32) / (height as f32);
+        
+        // Unproject the point on the near and far planes, which works the
+        // same for orthographic views, where the rays all run parallel
+        let (model, view_matrix, projection) = self.calc_matrices(view, aspect);
+        match (projection * view_matrix * model).inverse() {
+            Some(inverse) => {
+                let unproject = |z: f32| {
+                    let p = inverse.transform(&Vec4::new(ndc_x, ndc_y, z, 1.0));
+                    Vec3::new(p.x / p.w, p.y / p.w, p.z / p.w)
+                };
+                let (near, far) = (unproject(-1.0), unproject(1.0));
+                Ray::new(near.clone(), (far - near).normalize())
+            },
+            None => Ray::new(self.eye.clone(), (self.center - self.eye).normalize())
+        }
     }
     
     pub fn look_at(&mut self, eye: Vec3<f32>, center: Vec3<f32>, up: Vec3<f32>) {
@@ -87,26 +234,7 @@ impl Camera {
     }
     
     pub fn calc_view(&self) -> Mat4<f32> {
-        
-        let f = (self.center - self.eye).normalize();
-        let s = f.cross(&self.up.normalize()).normalize();
-        let u = s.cross(&f);
-        
-        let mut result = Mat4::from_elem(1.0f32);
-        result.data[0][0] =  s.x.clone();
-        result.data[1][0] =  s.y.clone();
-        result.data[2][0] =  s.z.clone();
-        result.data[0][1] =  u.x.clone();
-        result.data[1][1] =  u.y.clone();
-        result.data[2][1] =  u.z.clone();
-        result.data[0][2] = -f.x.clone();
-        result.data[1][2] = -f.y.clone();
-        result.data[2][2] = -f.z.clone();
-        result.data[3][0] = -s.dot(&self.eye).clone();
-        result.data[3][1] = -u.dot(&self.eye).clone();
-        result.data[3][2] =  f.dot(&self.eye).clone();
-        
-        result
+        Mat4::look_at(self.eye.clone(), self.center.clone(), self.up.clone())
     }
     
     pub fn perspective(&mut self, fovy: f32, z_near: f32, z_far: f32) {
@@ -115,37 +243,206 @@ impl Camera {
         self.z_far = z_far;
     }
     
-    pub fn calc_projection(&self) -> Mat4<f32> {
-        let tan_half_fovy = (self.fovy / 2.0).tan();
-        
-        let mut result = Mat4::from_elem(0.0f32);
-        result.data[0][0] = 1.0 / (self.aspect * tan_half_fovy);
-        result.data[1][1] = 1.0 / tan_half_fovy;
-        result.data[2][2] = - (self.z_far + self.z_near) / (self.z_far - self.z_near);
-        result.data[2][3] = -1.0;
-        result.data[3][2] = - (2.0 * self.z_far * self.z_near) / (self.z_far - self.z_near);
+    pub fn calc_projection(&self, aspect: f32) -> Mat4<f32> {
+        Mat4::perspective(self.fovy, aspect, self.z_near, self.z_far)
+    }
+    
+    pub fn set_layout(&mut self, layout: ViewLayout) {
+        self.layout = layout;
+    }
+    
+    /// Each view on screen with its (left, top, width, height) in
+    /// framebuffer pixels. The first is the one labels are drawn for.
+    pub fn panes(&self) -> ~[(View, (int, int, int, int))] {
+        let (width, height) = (self.width, self.height);
+        match self.layout {
+            Single(view) => ~[(view, (0, 0, width, height))],
+            Split => {
+                let (half_width, half_height) = (width / 2, height / 2);
+                ~[(Perspective, (0, 0, half_width, height)),
+                  (Plan, (half_width, 0, width - half_width, half_height)),
+                  (Elevation, (half_width, half_height, width - half_width, height - half_height))]
+            }
+        }
+    }
+    
+    /// The model, view and projection matrices for a view drawn at `aspect`.
+    /// Orthographic views ignore the user's pose so they always line up with
+    /// the layout.
+    pub fn calc_matrices(&self, view: View, aspect: f32) -> (Mat4<f32>, Mat4<f32>, Mat4<f32>) {
+        match view {
+            Perspective => (self.calc_model(), self.calc_view(), self.calc_projection(aspect)),
+            _ => {
+                let (view_matrix, projection) = ortho_matrices(&self.bounds, view, aspect);
+                (Mat4::ident(), view_matrix, projection)
+            }
+        }
+    }
+    
+    /// Switches the scene to another shader, keeping the current one if
+    /// the new one doesn't build.
+    pub fn set_shader(&mut self, source: &ShaderSource) -> Result<(), ShaderError> {
+        match Program::load(source) {
+            Ok(program) => { self.program = program; Ok(()) },
+            Err(error) => Err(error)
+        }
+    }
+    
+    /// Recompiles shaders whose sources have changed. Errors are logged and
+    /// the old program stays in use until the file is fixed.
+    pub fn reload_shaders(&mut self) {
+        let now = time::precise_time_s();
+        if now - self.last_reload_check < RELOAD_INTERVAL {
+            return;
+        }
+        self.last_reload_check = now;
         
-        result
+        if self.program.check_changed() {
+            let source = self.program.source.clone();
+            match self.set_shader(&source) {
+                Ok(()) => printfln!("Reloaded %s", source.name()),
+                Err(error) => error!("Keeping the old %s: %s", source.name(), error.to_str())
+            }
+        }
+        if self.overlay_program.check_changed() {
+            let loaded = Program::load(&self.overlay_program.source);
+            match loaded {
+                Ok(program) => self.overlay_program = program,
+                Err(error) => error!("Keeping the old %s: %s", self.overlay_program.name(), error.to_str())
+            }
+        }
     }
     
     pub fn add_mesh(&mut self, mesh: Mesh) {
+        self.bounds = match (self.bounds.clone(), mesh.bounds()) {
+            (Some(bounds), Some(other)) => Some(bounds.union(&other)),
+            (bounds, None) => bounds,
+            (None, other) => other
+        };
         self.meshes.push(mesh);
     }
     
+    pub fn rod<'a>(&'a self, index: uint) -> Option<&'a Mesh> {
+        self.meshes.iter().find_(|mesh| mesh.rod == Some(index))
+    }
+    
+    pub fn rod_mut<'a>(&'a mut self, index: uint) -> Option<&'a mut Mesh> {
+        self.meshes.mut_iter().find_(|mesh| mesh.rod == Some(index))
+    }
+    
     pub fn draw(&mut self) {
-        let model = self.calc_model();
-        let view = self.calc_view();
-        let projection = self.calc_projection();
+        self.render();
+        self.present();
+    }
+    
+    pub fn present(&mut self) {
+        self.window.swap_buffers();
+    }
+    
+    /// Framebuffer pixels per window coordinate; 2 on most high-DPI displays.
+    pub fn pixel_ratio(&self) -> f32 {
+        let (window_width, _) = self.window.get_size();
+        if window_width > 0 { (self.width as f32) / (window_width as f32) } else { 1.0 }
+    }
+    
+    /// Where `point` lands in the first view on screen, in framebuffer
+    /// pixels from the top left, or `None` if it's behind the camera.
+    pub fn project(&self, point: Vec3<f32>) -> Option<(f32, f32)> {
+        let (view, (left, top, width, height)) = self.panes()[0];
+        let (model, view, projection) = self.calc_matrices(view, (width as f32) / (height as f32));
+        let mvp = projection * view * model;
+        let clip = mvp.transform(&Vec4::new(point.x, point.y, point.z, 1.0));
+        if clip.w <= 0.0 {
+            return None;
+        }
+        Some((left as f32 + (clip.x / clip.w + 1.0) / 2.0 * (width as f32),
+              top as f32 + (1.0 - clip.y / clip.w) / 2.0 * (height as f32)))
+    }
+    
+    /// Draws meshes laid out in framebuffer pixels from the top left, on
+    /// top of everything else.
+    pub fn draw_overlay(&mut self, meshes: &mut [Mesh]) {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let pixels = Mat4::new([2.0 / width, 0.0, 0.0, 0.0], [0.0, -2.0 / height, 0.0, 0.0],
+                               [0.0, 0.0, 1.0, 0.0], [-1.0, 1.0, 0.0, 1.0]);
+        self.overlay_program.bind();
+        self.overlay_program.uniforms.view.set(Mat4::ident());
+        self.overlay_program.uniforms.projection.set(pixels);
+        
+        check!(es::disable(es::DEPTH_TEST));
+        for meshes.mut_iter().advance |mesh| {
+            if !mesh.uploaded() { mesh.upload(); }
+            mesh.draw(Mat4::ident(), &self.overlay_program.attribs, &self.overlay_program.uniforms);
+        }
+        check!(es::enable(es::DEPTH_TEST));
+    }
+    
+    /// Draws into the back buffer without presenting it, so it can be read
+    /// back first.
+    pub fn render(&mut self) {
+        for self.meshes.mut_iter().advance |mesh| {
+            mesh.poll();
+            if !mesh.uploaded() { mesh.upload(); }
+        }
         
-        self.uniforms.find_equiv(&("v")).get().update_mat4_f32(view);
-        self.uniforms.find_equiv(&("p")).get().update_mat4_f32(projection);
+        let panes = self.panes();
+        check!(es::enable(es::SCISSOR_TEST));
+        for panes.iter().advance |&(view, (left, top, width, height))| {
+            if width <= 0 || height <= 0 {
+                loop;
+            }
+            // GL counts rows from the bottom
+            let bottom = self.height - top - height;
+            check!(es::viewport(left as es::GLint, bottom as es::GLint, width as es::GLsizei, height as es::GLsizei));
+            check!(es::scissor(left as es::GLint, bottom as es::GLint, width as es::GLsizei, height as es::GLsizei));
+            // A slightly lighter background tells the orthographic views apart
+            match view {
+                Perspective => es::clear_color(0.9, 0.9, 0.9, 1.0),
+                _ => es::clear_color(0.95, 0.95, 0.95, 1.0)
+            }
+            es::clear(es::COLOR_BUFFER_BIT | es::DEPTH_BUFFER_BIT);
+            
+            let (model, view, projection) = self.calc_matrices(view, (width as f32) / (height as f32));
+            self.render_view(model, view, projection);
+        }
+        check!(es::disable(es::SCISSOR_TEST));
+        check!(es::viewport(0, 0, self.width as es::GLsizei, self.height as es::GLsizei));
+    }
+    
+    fn render_view(&mut self, model: Mat4<f32>, view: Mat4<f32>, projection: Mat4<f32>) {
+        self.program.bind();
+        self.program.uniforms.view.set(view);
+        self.program.uniforms.projection.set(projection);
         
+        // Opaque meshes go first and fill the depth buffer. Translucent ones
+        // are blended over them from back to front without writing depth,
+        // so nearer rods never hide the ones behind them.
+        let view_model = view * model;
+        let mut translucent: ~[(f32, uint)] = ~[];
         for uint::range(0, self.meshes.len()) |i| {
-            if !self.meshes[i].uploaded() { self.meshes[i].upload(); }
-            self.meshes[i].draw(model, self.attribs, self.uniforms);
+            if self.meshes[i].is_translucent() {
+                let center = self.meshes[i].center();
+                let depth = view_model.transform(&Vec4::new(center.x, center.y, center.z, 1.0)).z;
+                translucent.push((depth, i));
+            } else {
+                self.meshes[i].draw(model, &self.program.attribs, &self.program.uniforms);
+            }
         }
         
-        self.window.swap_buffers();
+        // View space looks down -z, so the farthest mesh has the lowest z
+        sort::quick_sort(translucent, |&(a, _), &(b, _)| a <= b);
+        
+        check!(es::depth_mask(false));
+        check!(es::enable(es::CULL_FACE));
+        for translucent.iter().advance |&(_, i)| {
+            // Each mesh's far side, then its near side
+            check!(es::cull_face(es::FRONT));
+            self.meshes[i].draw(model, &self.program.attribs, &self.program.uniforms);
+            check!(es::cull_face(es::BACK));
+            self.meshes[i].draw(model, &self.program.attribs, &self.program.uniforms);
+        }
+        check!(es::disable(es::CULL_FACE));
+        check!(es::depth_mask(true));
     }
     
     pub fn is_key_down(&self, key: c_int) -> bool {
@@ -155,8 +452,85 @@ impl Camera {
         }
     }
     
-    pub fn resize(&mut self, _size: (int, int)) {
-        //let (width, height) = size;
+    pub fn cursor_pos(&self) -> (float, float) {
+        self.window.get_cursor_pos()
+    }
+    
+    /// Like `was_key_pressed`, for the left mouse button.
+    pub fn was_clicked(&mut self) -> bool {
+        let down = match self.window.get_mouse_button(glfw::MOUSE_BUTTON_LEFT) {
+            glfw::PRESS => true,
+            _ => false,
+        };
+        let clicked = down && !self.mouse_held;
+        self.mouse_held = down;
+        clicked
+    }
+    
+    pub fn select_rod(&mut self, index: Option<uint>) {
+        for self.meshes.mut_iter().advance |mesh| {
+            mesh.highlighted = mesh.rod.is_some() && mesh.rod == index;
+        }
+    }
+    
+    /// Returns true only on the frame a key goes down, so toggles don't
+    /// flip back and forth while the key is held.
+    pub fn was_key_pressed(&mut self, key: c_int) -> bool {
+        let down = self.is_key_down(key);
+        let was_down = self.keys_held.contains(&key);
+        if down && !was_down {
+            self.keys_held.insert(key);
+        } else if !down && was_down {
+            self.keys_held.remove(&key);
+        }
+        down && !was_down
+    }
+    
+    /// Takes the framebuffer size, which differs from the window size on
+    /// high-DPI displays.
+    pub fn resize(&mut self, size: (int, int)) {
+        let (width, height) = size;
+        // Minimized windows report a zero-sized framebuffer
+        if width <= 0 || height <= 0 {
+            return;
+        }
+        self.width = width;
+        self.height = height;
+        check!(es::viewport(0, 0, width as es::GLsizei, height as es::GLsizei));
+    }
+    
+    /// Picks up any change in framebuffer size since the last frame.
+    pub fn update_size(&mut self) {
+        let size = self.window.get_framebuffer_size();
+        if size != (self.width, self.height) {
+            self.resize(size);
+        }
+    }
+    
+    /// Moves the window to the top left of the primary monitor and sizes
+    /// it to the monitor's resolution, or puts it back where it was. This
+    /// isn't true fullscreen: GLFW can only make a window fullscreen when
+    /// creating it, and that would mean a new context. The title bar and
+    /// borders stay, so the window manager may push the bottom edge off
+    /// the screen or shrink the window to fit.
+    pub fn toggle_cover_monitor(&mut self) {
+        if self.covering {
+            let (x, y, width, height) = self.windowed_rect;
+            self.window.set_pos(x, y);
+            self.window.set_size(width, height);
+        } else {
+            let mode = match glfw::Monitor::get_primary().get_video_mode() {
+                Some(mode) => mode,
+                None => { error!("Couldn't query the primary monitor"); return; }
+            };
+            let (x, y) = self.window.get_pos();
+            let (width, height) = self.window.get_size();
+            self.windowed_rect = (x, y, width, height);
+            self.window.set_pos(0, 0);
+            self.window.set_size(mode.width as int, mode.height as int);
+        }
+        self.covering = !self.covering;
+        self.update_size();
     }
     
     
@@ -164,3 +538,102 @@ impl Camera {
         self.window.should_close()
     }
 }
+
+/// View and projection matrices for an orthographic view framing `bounds`.
+fn ortho_matrices(bounds: &Option<Aabb>, view: View, aspect: f32) -> (Mat4<f32>, Mat4<f32>) {
+    let bounds = match *bounds {
+        Some(ref bounds) => bounds.clone(),
+        None => Aabb::new(Vec3::new(-1.0f32, -1.0, -1.0), Vec3::new(1.0f32, 1.0, 1.0))
+    };
+    let center = bounds.center();
+    let size = bounds.size();
+    // Looking down, scene z (layout y) runs toward the bottom of the
+    // screen; anything else would mirror the layout
+    let (forward, up) = match view {
+        Plan => (Vec3::new(0.0f32, -1.0, 0.0), Vec3::new(0.0f32, 0.0, -1.0)),
+        _ => (Vec3::new(0.0f32, 0.0, -1.0), Vec3::new(0.0f32, 1.0, 0.0))
+    };
+    let right = forward.cross(&up);
+    let span = |axis: &Vec3<f32>| (axis.x * size.x).abs() + (axis.y * size.y).abs() + (axis.z * size.z).abs();
+    
+    let half_height = (span(&up) / 2.0).max(&(span(&right) / 2.0 / aspect)) * ORTHO_MARGIN;
+    let half_width = half_height * aspect;
+    let reach = span(&forward) / 2.0 + 1.0;
+    let eye = center - forward * reach;
+    (Mat4::look_at(eye, center, up),
+     Mat4::orthographic(-half_width, half_width, -half_height, half_height, 0.0, 2.0 * reach))
+}
+
+#[cfg(test)]
+fn project(mat: &Mat4<f32>, p: &Vec3<f32>) -> Vec3<f32> {
+    let p = mat.transform(&Vec4::new(p.x, p.y, p.z, 1.0));
+    Vec3::new(p.x / p.w, p.y / p.w, p.z / p.w)
+}
+
+#[test]
+fn test_pose_model() {
+    let pose = Pose { translation: Vec3::new(1.0f32, -2.0, 3.0),
+                      orientation: Quat::from_euler(0.4, -0.7, 1.2) };
+    let model = pose.calc_model();
+    let p = Vec3::new(0.5f32, 0.25, -1.0);
+    let expected = pose.orientation.rotate(&p) + pose.translation;
+    let moved = project(&model, &p);
+    assert!((moved - expected).dot(&(moved - expected)) < 1e-8);
+    // The inverse the camera used to build by hand for picking
+    let by_hand = pose.orientation.conjugate().to_mat4().translate(pose.translation * -1.0f32);
+    let inverse = model.inverse().unwrap();
+    for uint::range(0, 4) |i| {
+        for uint::range(0, 4) |j| {
+            assert!((inverse.data[i][j] - by_hand.data[i][j]).abs() < 1e-5);
+        }
+    }
+}
+
+#[test]
+fn test_ortho_framing() {
+    let bounds = Some(Aabb::new(Vec3::new(-3.0f32, 0.0, -1.0), Vec3::new(5.0f32, 2.0, 1.0)));
+    for [Plan, Elevation].iter().advance |&view| {
+        let (view_matrix, projection) = ortho_matrices(&bounds, view, 1.5);
+        let mat = projection * view_matrix;
+        // Every corner of the scene is on screen and inside the depth range
+        for uint::range(0, 8) |corner| {
+            let p = Vec3::new(if corner & 1 == 0 { -3.0f32 } else { 5.0 }, if corner & 2 == 0 { 0.0 } else { 2.0 },
+                              if corner & 4 == 0 { -1.0 } else { 1.0 });
+            let ndc = project(&mat, &p);
+            assert!(ndc.x.abs() <= 1.0 && ndc.y.abs() <= 1.0 && ndc.z.abs() <= 1.0);
+        }
+    }
+    // From above, layout x runs right and layout y down the screen
+    let (view_matrix, projection) = ortho_matrices(&bounds, Plan, 1.5);
+    let mat = projection * view_matrix;
+    let origin = project(&mat, &Vec3::new(0.0f32, 1.0, 0.0));
+    let right = project(&mat, &Vec3::new(1.0f32, 1.0, 0.0));
+    let down = project(&mat, &Vec3::new(0.0f32, 1.0, 1.0));
+    assert!(right.x > origin.x && (right.y - origin.y).abs() < 1e-6);
+    assert!(down.y < origin.y && (down.x - origin.x).abs() < 1e-6);
+}
+
+#[test]
+fn test_startup_matrices() {
+    // What calc_matrices gives in the perspective view of an 800x600
+    // window before anything moves, pinned so changes to the matrix code
+    // show up here rather than as a subtly different picture
+    let pose = Pose { translation: Vec3::new(0.0f32, 0.0, 0.0), orientation: Quat::ident() };
+    let (eye, center, up) = start_view();
+    let mvp = Mat4::perspective(START_FOVY, 800.0 / 600.0, START_Z_NEAR, START_Z_FAR) *
+              Mat4::look_at(eye, center, up) * pose.calc_model();
+    // Row by row, where data is column by column
+    let expected = [[-1.810662f32, 0.0, 0.0, 0.0],
+                    [0.0, -1.339166, 2.008749, 1.339166],
+                    [0.0, -0.848859, -0.565906, 0.646839],
+                    [0.0, -0.832050, -0.554700, 0.832050]];
+    for uint::range(0, 4) |row| {
+        for uint::range(0, 4) |col| {
+            assert!((mvp.data[col][row] - expected[row][col]).abs() < 1e-4,
+                    fmt!("row %u, column %u: %f", row, col, mvp.data[col][row] as float));
+        }
+    }
+    // The point looked at is in the middle of the screen
+    let middle = project(&mvp, &Vec3::new(0.0f32, -2.0, -2.0));
+    assert!(middle.x.abs() < 1e-6 && middle.y.abs() < 1e-6 && middle.z.abs() < 1.0);
+}
@@ -0,0 +1,652 @@
+use std::{str, uint, util, vec};
+use std::cast::transmute;
+use std::io;
+use extra::json;
+
+use math::{Mat3, Mat4, Quat, Vec3, Vec4};
+use config;
+
+/// The GLB container's magic number and chunk types, as little-endian words.
+static GLB_MAGIC: u32 = 0x46546C67;
+static GLB_JSON: u32 = 0x4E4F534A;
+static GLB_BIN: u32 = 0x004E4942;
+
+static MODE_TRIANGLES: uint = 4;
+
+/// One glTF primitive, already moved into place by the nodes above it.
+pub struct Primitive {
+    name: ~str,
+    positions: ~[Vec4<f32>],
+    /// Empty if the file didn't give any.
+    normals: ~[Vec3<f32>],
+    texcoords: ~[Vec3<f32>],
+    /// The material's base color times any vertex colors.
+    colors: ~[Vec4<f32>],
+    indices: ~[u32],
+}
+
+/// Reads the default scene of a `.gltf` or `.glb` file. Buffers can be in
+/// the GLB, next to the file, or embedded as base64 data URIs. Only
+/// triangles are kept, and materials are reduced to their base color
+/// factor.
+pub fn load(path_str: &str) -> Result<~[Primitive], ~str> {
+    let (doc, bin) = if path_str.ends_with(".glb") || path_str.ends_with(".GLB") {
+        match io::read_whole_file(&Path(path_str)) {
+            Ok(bytes) => match split_glb(bytes) {
+                Ok(parts) => parts,
+                Err(error) => return Err(fmt!("%s: %s", path_str, error))
+            },
+            Err(error) => return Err(fmt!("%s: %s", path_str, error))
+        }
+    } else {
+        match config::read_json_file(path_str) {
+            Ok(doc) => (doc, None),
+            Err(error) => return Err(error)
+        }
+    };
+    let doc = match doc {
+        json::Object(obj) => obj,
+        _ => return Err(fmt!("%s: expected an object", path_str))
+    };
+    match load_scene(&*doc, bin, &Path(path_str).dir_path()) {
+        Ok(primitives) => Ok(primitives),
+        Err(error) => Err(fmt!("%s: %s", path_str, error))
+    }
+}
+
+/// Splits a GLB into its JSON document and binary chunk.
+fn split_glb(bytes: &[u8]) -> Result<(json::Json, Option<~[u8]>), ~str> {
+    if bytes.len() < 20 || read_u32(bytes, 0) != GLB_MAGIC {
+        return Err(~"not a GLB file");
+    }
+    if read_u32(bytes, 4) != 2 {
+        return Err(~"only glTF 2.0 is supported");
+    }
+    let mut doc = None;
+    let mut bin = None;
+    let mut pos = 12u;
+    while pos + 8 <= bytes.len() {
+        let (length, kind) = (read_u32(bytes, pos) as uint, read_u32(bytes, pos + 4));
+        let start = pos + 8;
+        if start + length > bytes.len() {
+            return Err(~"truncated chunk");
+        }
+        let data = bytes.slice(start, start + length);
+        if kind == GLB_JSON && doc.is_none() {
+            doc = match json::from_str(str::from_bytes(data)) {
+                Ok(json) => Some(json),
+                Err(error) => return Err(fmt!("%u:%u: %s", error.line, error.col, *error.msg))
+            };
+        } else if kind == GLB_BIN && bin.is_none() {
+            bin = Some(vec::from_slice(data));
+        }
+        // Chunks are padded to four bytes
+        pos = start + (length + 3) / 4 * 4;
+    }
+    match doc {
+        Some(doc) => Ok((doc, bin)),
+        None => Err(~"no JSON chunk")
+    }
+}
+
+fn load_scene(doc: &json::Object, bin: Option<~[u8]>, dir: &Path) -> Result<~[Primitive], ~str> {
+    let mut bin = bin;
+    let mut buffers = ~[];
+    for list(doc, "buffers").iter().enumerate().advance |(i, buffer)| {
+        let buffer = match object(buffer, "buffer", i) {
+            Ok(buffer) => buffer,
+            Err(error) => return Err(error)
+        };
+        let data = match buffer.find_equiv(&("uri")) {
+            Some(&json::String(ref uri)) if uri.starts_with("data:") => match uri.find(',') {
+                Some(comma) => match decode_base64(uri.slice_from(comma + 1)) {
+                    Some(data) => data,
+                    None => return Err(fmt!("buffer %u: bad base64 data", i))
+                },
+                None => return Err(fmt!("buffer %u: bad data URI", i))
+            },
+            Some(&json::String(ref uri)) => match io::read_whole_file(&dir.push(*uri)) {
+                Ok(data) => data,
+                Err(error) => return Err(fmt!("buffer %u: %s", i, error))
+            },
+            Some(_) => return Err(fmt!("buffer %u: \"uri\" should be a string", i)),
+            // Only the GLB's own chunk has no URI
+            None => match util::replace(&mut bin, None) {
+                Some(data) => data,
+                None => return Err(fmt!("buffer %u has no data", i))
+            }
+        };
+        buffers.push(data);
+    }
+
+    let nodes = list(doc, "nodes");
+    let roots = match doc.find_equiv(&("scenes")) {
+        Some(&json::List(ref scenes)) if scenes.len() > 0 => {
+            let scene = match optional_index(doc, "scene") {
+                Ok(Some(scene)) if scene < scenes.len() => scene,
+                Ok(Some(scene)) => return Err(fmt!("scene %u doesn't exist", scene)),
+                Ok(None) => 0,
+                Err(error) => return Err(error)
+            };
+            match object(&scenes[scene], "scene", scene) {
+                Ok(scene) => match indices(scene, "nodes") {
+                    Ok(roots) => roots,
+                    Err(error) => return Err(error)
+                },
+                Err(error) => return Err(error)
+            }
+        },
+        _ => {
+            // Without scenes, every node no one else claims is a root
+            let mut claimed = vec::from_elem(nodes.len(), false);
+            for nodes.iter().advance |node| {
+                match node {
+                    &json::Object(ref node) => match indices(&**node, "children") {
+                        Ok(children) => {
+                            for children.iter().advance |&child| {
+                                if child < claimed.len() {
+                                    claimed[child] = true;
+                                }
+                            }
+                        },
+                        Err(error) => return Err(error)
+                    },
+                    _ => {}
+                }
+            }
+            let mut roots = ~[];
+            for uint::range(0, nodes.len()) |i| {
+                if !claimed[i] {
+                    roots.push(i);
+                }
+            }
+            roots
+        }
+    };
+
+    let mut primitives = ~[];
+    for roots.iter().advance |&root| {
+        match visit(doc, buffers, root, &Mat4::ident(), 0, &mut primitives) {
+            Ok(()) => {},
+            Err(error) => return Err(error)
+        }
+    }
+    Ok(primitives)
+}
+
+/// Adds the primitives under node `index`, whose parent's transform is
+/// `parent`, to `primitives`.
+fn visit(doc: &json::Object, buffers: &[~[u8]], index: uint, parent: &Mat4<f32>, depth: uint,
+         primitives: &mut ~[Primitive]) -> Result<(), ~str> {
+    let nodes = list(doc, "nodes");
+    // A valid hierarchy can't be deeper than it has nodes
+    if depth > nodes.len() {
+        return Err(~"node hierarchy has a cycle");
+    }
+    if index >= nodes.len() {
+        return Err(fmt!("node %u doesn't exist", index));
+    }
+    let node = match object(&nodes[index], "node", index) {
+        Ok(node) => node,
+        Err(error) => return Err(error)
+    };
+    let transform = match node_transform(node) {
+        Ok(local) => *parent * local,
+        Err(error) => return Err(fmt!("node %u: %s", index, error))
+    };
+
+    match optional_index(node, "mesh") {
+        Ok(Some(mesh)) => {
+            let name = match config::find_string(node, "name") {
+                Ok(name) => name,
+                Err(_) => fmt!("node %u", index)
+            };
+            match load_mesh(doc, buffers, mesh, name, &transform, primitives) {
+                Ok(()) => {},
+                Err(error) => return Err(fmt!("mesh %u: %s", mesh, error))
+            }
+        },
+        Ok(None) => {},
+        Err(error) => return Err(fmt!("node %u: %s", index, error))
+    }
+
+    let children = match indices(node, "children") {
+        Ok(children) => children,
+        Err(error) => return Err(fmt!("node %u: %s", index, error))
+    };
+    for children.iter().advance |&child| {
+        match visit(doc, buffers, child, &transform, depth + 1, primitives) {
+            Ok(()) => {},
+            Err(error) => return Err(error)
+        }
+    }
+    Ok(())
+}
+
+/// A node's `matrix`, or its translation, rotation and scale combined.
+fn node_transform(node: &json::Object) -> Result<Mat4<f32>, ~str> {
+    if node.contains_key_equiv(&("matrix")) {
+        return match numbers(node, "matrix", 16) {
+            Ok(m) => Ok(Mat4::new([m[0], m[1], m[2], m[3]], [m[4], m[5], m[6], m[7]],
+                                  [m[8], m[9], m[10], m[11]], [m[12], m[13], m[14], m[15]])),
+            Err(error) => Err(error)
+        };
+    }
+    let translation = match optional_numbers(node, "translation", ~[0.0, 0.0, 0.0]) {
+        Ok(t) => t,
+        Err(error) => return Err(error)
+    };
+    let rotation = match optional_numbers(node, "rotation", ~[0.0, 0.0, 0.0, 1.0]) {
+        Ok(r) => r,
+        Err(error) => return Err(error)
+    };
+    let scale = match optional_numbers(node, "scale", ~[1.0, 1.0, 1.0]) {
+        Ok(s) => s,
+        Err(error) => return Err(error)
+    };
+    // Files aren't always careful to keep rotations unit length
+    let r = Quat::new(rotation[0], rotation[1], rotation[2], rotation[3]).normalize().to_mat3().data;
+    Ok(Mat4::new([r[0][0] * scale[0], r[0][1] * scale[0], r[0][2] * scale[0], 0.0],
+                 [r[1][0] * scale[1], r[1][1] * scale[1], r[1][2] * scale[1], 0.0],
+                 [r[2][0] * scale[2], r[2][1] * scale[2], r[2][2] * scale[2], 0.0],
+                 [translation[0], translation[1], translation[2], 1.0]))
+}
+
+fn load_mesh(doc: &json::Object, buffers: &[~[u8]], index: uint, name: ~str, transform: &Mat4<f32>,
+             primitives: &mut ~[Primitive]) -> Result<(), ~str> {
+    let meshes = list(doc, "meshes");
+    if index >= meshes.len() {
+        return Err(~"doesn't exist");
+    }
+    let mesh = match object(&meshes[index], "mesh", index) {
+        Ok(mesh) => mesh,
+        Err(error) => return Err(error)
+    };
+    let normal_matrix = Mat3::from_four(*transform).trans_inv();
+    let parts = list(mesh, "primitives");
+    for parts.iter().enumerate().advance |(i, part)| {
+        let part = match object(part, "primitive", i) {
+            Ok(part) => part,
+            Err(error) => return Err(error)
+        };
+        match optional_index(part, "mode") {
+            Ok(Some(mode)) if mode != MODE_TRIANGLES => {
+                warn!(fmt!("%s: skipping a primitive that isn't triangles", name));
+                loop;
+            },
+            Ok(_) => {},
+            Err(error) => return Err(fmt!("primitive %u: %s", i, error))
+        }
+        let name = if parts.len() > 1 { fmt!("%s.%u", name, i) } else { name.clone() };
+        match load_primitive(doc, buffers, part, name, transform, &normal_matrix) {
+            Ok(primitive) => primitives.push(primitive),
+            Err(error) => return Err(fmt!("primitive %u: %s", i, error))
+        }
+    }
+    Ok(())
+}
+
+fn load_primitive(doc: &json::Object, buffers: &[~[u8]], part: &json::Object, name: ~str,
+                  transform: &Mat4<f32>, normal_matrix: &Mat3<f32>) -> Result<Primitive, ~str> {
+    let attributes = match part.find_equiv(&("attributes")) {
+        Some(&json::Object(ref attributes)) => attributes,
+        _ => return Err(~"missing \"attributes\"")
+    };
+    let attribute = |key: &str, sizes: &[uint]| -> Result<Option<(~[float], uint)>, ~str> {
+        match optional_index(&**attributes, key) {
+            Ok(Some(accessor)) => match read_accessor(doc, buffers, accessor) {
+                Ok((values, size)) if sizes.contains(&size) => Ok(Some((values, size))),
+                Ok(_) => Err(fmt!("%s has the wrong type", key)),
+                Err(error) => Err(fmt!("%s: %s", key, error))
+            },
+            Ok(None) => Ok(None),
+            Err(error) => Err(error)
+        }
+    };
+
+    let positions = match attribute("POSITION", [3]) {
+        Ok(Some((values, _))) => do vec::from_fn(values.len() / 3) |i| {
+            transform.transform(&Vec4::new(values[i * 3] as f32, values[i * 3 + 1] as f32,
+                                           values[i * 3 + 2] as f32, 1.0))
+        },
+        Ok(None) => return Err(~"missing POSITION"),
+        Err(error) => return Err(error)
+    };
+    let count = positions.len();
+    let normals = match attribute("NORMAL", [3]) {
+        Ok(Some((values, _))) if values.len() == count * 3 => do vec::from_fn(count) |i| {
+            let (x, y, z) = (values[i * 3] as f32, values[i * 3 + 1] as f32, values[i * 3 + 2] as f32);
+            let m = &normal_matrix.data;
+            Vec3::new(m[0][0] * x + m[1][0] * y + m[2][0] * z,
+                      m[0][1] * x + m[1][1] * y + m[2][1] * z,
+                      m[0][2] * x + m[1][2] * y + m[2][2] * z).normalize()
+        },
+        Ok(Some(_)) => return Err(~"NORMAL and POSITION counts differ"),
+        Ok(None) => ~[],
+        Err(error) => return Err(error)
+    };
+    // glTF puts the origin of texture space at the top left, OBJ and GL at
+    // the bottom left
+    let texcoords = match attribute("TEXCOORD_0", [2]) {
+        Ok(Some((values, _))) if values.len() == count * 2 => do vec::from_fn(count) |i| {
+            Vec3::new(values[i * 2] as f32, 1.0 - values[i * 2 + 1] as f32, 0.0)
+        },
+        Ok(Some(_)) => return Err(~"TEXCOORD_0 and POSITION counts differ"),
+        Ok(None) => ~[],
+        Err(error) => return Err(error)
+    };
+
+    let base = match base_color(doc, part) {
+        Ok(base) => base,
+        Err(error) => return Err(error)
+    };
+    let colors = match attribute("COLOR_0", [3, 4]) {
+        Ok(Some((values, size))) if values.len() == count * size => do vec::from_fn(count) |i| {
+            let alpha = if size == 4 { values[i * 4 + 3] as f32 } else { 1.0 };
+            Vec4::new(base.x * values[i * size] as f32, base.y * values[i * size + 1] as f32,
+                      base.z * values[i * size + 2] as f32, base.w * alpha)
+        },
+        Ok(Some(_)) => return Err(~"COLOR_0 and POSITION counts differ"),
+        Ok(None) => vec::from_elem(count, base),
+        Err(error) => return Err(error)
+    };
+
+    let indices = match optional_index(part, "indices") {
+        Ok(Some(accessor)) => match read_accessor(doc, buffers, accessor) {
+            Ok((values, 1)) => do values.map |&value| { value as u32 },
+            Ok(_) => return Err(~"indices should be scalars"),
+            Err(error) => return Err(fmt!("indices: %s", error))
+        },
+        Ok(None) => vec::from_fn(count, |i| i as u32),
+        Err(error) => return Err(error)
+    };
+    if indices.len() % 3 != 0 {
+        return Err(~"index count isn't a multiple of three");
+    }
+    for indices.iter().advance |&index| {
+        if index as uint >= count {
+            return Err(fmt!("index %u is out of range", index as uint));
+        }
+    }
+
+    Ok(Primitive { name: name, positions: positions, normals: normals, texcoords: texcoords,
+                   colors: colors, indices: indices })
+}
+
+/// The primitive's material's `baseColorFactor`, opaque unless the
+/// material blends.
+fn base_color(doc: &json::Object, part: &json::Object) -> Result<Vec4<f32>, ~str> {
+    let white = Vec4::new(1.0f32, 1.0, 1.0, 1.0);
+    let index = match optional_index(part, "material") {
+        Ok(Some(index)) => index,
+        Ok(None) => return Ok(white),
+        Err(error) => return Err(error)
+    };
+    let materials = list(doc, "materials");
+    if index >= materials.len() {
+        return Err(fmt!("material %u doesn't exist", index));
+    }
+    let material = match object(&materials[index], "material", index) {
+        Ok(material) => material,
+        Err(error) => return Err(error)
+    };
+    let factor = match material.find_equiv(&("pbrMetallicRoughness")) {
+        Some(&json::Object(ref pbr)) => match optional_numbers(&**pbr, "baseColorFactor", ~[1.0, 1.0, 1.0, 1.0]) {
+            Ok(factor) => factor,
+            Err(error) => return Err(fmt!("material %u: %s", index, error))
+        },
+        _ => ~[1.0, 1.0, 1.0, 1.0]
+    };
+    let blends = match config::find_string(material, "alphaMode") {
+        Ok(mode) => mode == ~"BLEND",
+        Err(_) => false
+    };
+    Ok(Vec4::new(factor[0], factor[1], factor[2], if blends { factor[3] } else { 1.0 }))
+}
+
+/// Reads accessor `index` as numbers, normalizing integer components if it
+/// says to. Returns them flattened along with the components per element.
+fn read_accessor(doc: &json::Object, buffers: &[~[u8]], index: uint) -> Result<(~[float], uint), ~str> {
+    let accessors = list(doc, "accessors");
+    if index >= accessors.len() {
+        return Err(fmt!("accessor %u doesn't exist", index));
+    }
+    let accessor = match object(&accessors[index], "accessor", index) {
+        Ok(accessor) => accessor,
+        Err(error) => return Err(error)
+    };
+    if accessor.contains_key_equiv(&("sparse")) {
+        return Err(fmt!("accessor %u is sparse, which isn't supported", index));
+    }
+    let count = match config::find_number(accessor, "count") {
+        Ok(count) => count as uint,
+        Err(error) => return Err(fmt!("accessor %u: %s", index, error))
+    };
+    let components = match config::find_string(accessor, "type") {
+        Ok(kind) => {
+            if kind == ~"SCALAR" { 1u }
+            else if kind == ~"VEC2" { 2 }
+            else if kind == ~"VEC3" { 3 }
+            else if kind == ~"VEC4" { 4 }
+            else { return Err(fmt!("accessor %u: unsupported type %s", index, kind)); }
+        },
+        Err(error) => return Err(fmt!("accessor %u: %s", index, error))
+    };
+    let component_type = match config::find_number(accessor, "componentType") {
+        Ok(kind) => kind as uint,
+        Err(error) => return Err(fmt!("accessor %u: %s", index, error))
+    };
+    let size = match component_type {
+        5120 | 5121 => 1u,
+        5122 | 5123 => 2,
+        5125 | 5126 => 4,
+        _ => return Err(fmt!("accessor %u: unknown component type %u", index, component_type))
+    };
+    let normalized = match accessor.find_equiv(&("normalized")) {
+        Some(&json::Boolean(normalized)) => normalized,
+        _ => false
+    };
+
+    // An accessor without a view is all zeros
+    let view_index = match optional_index(accessor, "bufferView") {
+        Ok(Some(view)) => view,
+        Ok(None) => return Ok((vec::from_elem(count * components, 0.0), components)),
+        Err(error) => return Err(fmt!("accessor %u: %s", index, error))
+    };
+    let views = list(doc, "bufferViews");
+    if view_index >= views.len() {
+        return Err(fmt!("buffer view %u doesn't exist", view_index));
+    }
+    let view = match object(&views[view_index], "buffer view", view_index) {
+        Ok(view) => view,
+        Err(error) => return Err(error)
+    };
+    let buffer = match config::find_number(view, "buffer") {
+        Ok(buffer) if (buffer as uint) < buffers.len() => {
+            let data = &buffers[buffer as uint];
+            data.slice(0, data.len())
+        },
+        Ok(buffer) => return Err(fmt!("buffer %u doesn't exist", buffer as uint)),
+        Err(error) => return Err(fmt!("buffer view %u: %s", view_index, error))
+    };
+    let offset_in = |obj: &json::Object| match config::find_number(obj, "byteOffset") {
+        Ok(offset) => offset as uint,
+        Err(_) => 0
+    };
+    let start = offset_in(view) + offset_in(accessor);
+    let stride = match config::find_number(view, "byteStride") {
+        Ok(stride) => stride as uint,
+        Err(_) => components * size
+    };
+    let view_end = offset_in(view) + match config::find_number(view, "byteLength") {
+        Ok(length) => length as uint,
+        Err(error) => return Err(fmt!("buffer view %u: %s", view_index, error))
+    };
+    if count > 0 && (start + (count - 1) * stride + components * size > view_end.min(&buffer.len())) {
+        return Err(fmt!("accessor %u runs past the end of its data", index));
+    }
+
+    let mut values = vec::with_capacity(count * components);
+    for uint::range(0, count) |i| {
+        for uint::range(0, components) |c| {
+            let pos = start + i * stride + c * size;
+            let value = match component_type {
+                5120 => {
+                    let v = buffer[pos] as i8 as float;
+                    if normalized { (v / 127.0).max(&-1.0) } else { v }
+                },
+                5121 => {
+                    let v = buffer[pos] as float;
+                    if normalized { v / 255.0 } else { v }
+                },
+                5122 => {
+                    let v = read_u16(buffer, pos) as i16 as float;
+                    if normalized { (v / 32767.0).max(&-1.0) } else { v }
+                },
+                5123 => {
+                    let v = read_u16(buffer, pos) as float;
+                    if normalized { v / 65535.0 } else { v }
+                },
+                5125 => read_u32(buffer, pos) as float,
+                _ => unsafe { transmute::<u32, f32>(read_u32(buffer, pos)) as float }
+            };
+            values.push(value);
+        }
+    }
+    Ok((values, components))
+}
+
+fn read_u16(bytes: &[u8], pos: uint) -> u16 {
+    bytes[pos] as u16 | (bytes[pos + 1] as u16 << 8)
+}
+
+fn read_u32(bytes: &[u8], pos: uint) -> u32 {
+    bytes[pos] as u32 | (bytes[pos + 1] as u32 << 8) | (bytes[pos + 2] as u32 << 16) |
+        (bytes[pos + 3] as u32 << 24)
+}
+
+/// Decodes standard base64, ignoring padding. None if anything else is in
+/// there.
+fn decode_base64(text: &str) -> Option<~[u8]> {
+    let mut bytes = vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut count = 0u;
+    for text.iter().advance |c| {
+        let value = match c {
+            'A'..'Z' => c as u32 - 'A' as u32,
+            'a'..'z' => c as u32 - 'a' as u32 + 26,
+            '0'..'9' => c as u32 - '0' as u32 + 52,
+            '+' => 62,
+            '/' => 63,
+            '=' => break,
+            _ => return None
+        };
+        bits = (bits << 6) | value;
+        count += 1;
+        if count == 4 {
+            bytes.push_all([(bits >> 16) as u8, (bits >> 8) as u8, bits as u8]);
+            bits = 0;
+            count = 0;
+        }
+    }
+    match count {
+        2 => bytes.push((bits >> 4) as u8),
+        3 => bytes.push_all([(bits >> 10) as u8, (bits >> 2) as u8]),
+        _ => {}
+    }
+    Some(bytes)
+}
+
+/// The list under `key`, or nothing if there isn't one.
+fn list<'a>(obj: &'a json::Object, key: &str) -> &'a [json::Json] {
+    match obj.find_equiv(&key) {
+        Some(&json::List(ref list)) => list.slice(0, list.len()),
+        _ => &[]
+    }
+}
+
+fn object<'a>(json: &'a json::Json, what: &str, index: uint) -> Result<&'a json::Object, ~str> {
+    match *json {
+        json::Object(ref obj) => Ok(&**obj),
+        _ => Err(fmt!("%s %u should be an object", what, index))
+    }
+}
+
+fn optional_index(obj: &json::Object, key: &str) -> Result<Option<uint>, ~str> {
+    match obj.find_equiv(&key) {
+        Some(&json::Number(num)) if num >= 0.0 => Ok(Some(num as uint)),
+        Some(_) => Err(fmt!("\"%s\" should be an index", key)),
+        None => Ok(None)
+    }
+}
+
+fn indices(obj: &json::Object, key: &str) -> Result<~[uint], ~str> {
+    let mut found = ~[];
+    for list(obj, key).iter().advance |item| {
+        match *item {
+            json::Number(num) if num >= 0.0 => found.push(num as uint),
+            _ => return Err(fmt!("\"%s\" should be a list of indices", key))
+        }
+    }
+    Ok(found)
+}
+
+fn numbers(obj: &json::Object, key: &str, len: uint) -> Result<~[f32], ~str> {
+    let mut found = ~[];
+    match obj.find_equiv(&key) {
+        Some(&json::List(ref items)) if items.len() == len => {
+            for items.iter().advance |item| {
+                match *item {
+                    json::Number(num) => found.push(num as f32),
+                    _ => return Err(fmt!("\"%s\" should be a list of %u numbers", key, len))
+                }
+            }
+        },
+        _ => return Err(fmt!("\"%s\" should be a list of %u numbers", key, len))
+    }
+    Ok(found)
+}
+
+fn optional_numbers(obj: &json::Object, key: &str, default: ~[f32]) -> Result<~[f32], ~str> {
+    if obj.contains_key_equiv(&key) { numbers(obj, key, default.len()) } else { Ok(default) }
+}
+
+#[test]
+fn test_base64() {
+    assert_eq!(decode_base64("AAEC/w=="), Some(~[0u8, 1, 2, 255]));
+    assert_eq!(decode_base64("AAECAw"), Some(~[0u8, 1, 2, 3]));
+    assert_eq!(decode_base64("AA.C"), None);
+}
+
+#[test]
+fn test_node_hierarchy() {
+    // A triangle under a node rotated a quarter turn about y, under one
+    // moved along x
+    let doc = "{
+        \"scenes\": [{\"nodes\": [0]}],
+        \"nodes\": [{\"translation\": [1, 0, 0], \"children\": [1]},
+                    {\"rotation\": [0, 0.7071068, 0, 0.7071068], \"mesh\": 0, \"name\": \"tri\"}],
+        \"meshes\": [{\"primitives\": [{\"attributes\": {\"POSITION\": 0}}]}],
+        \"accessors\": [{\"bufferView\": 0, \"componentType\": 5126, \"count\": 3, \"type\": \"VEC3\"}],
+        \"bufferViews\": [{\"buffer\": 0, \"byteLength\": 36}],
+        \"buffers\": [{\"byteLength\": 36,
+                       \"uri\": \"data:application/octet-stream;base64,AACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAAAAAAAAAAAAIA/\"}]
+    }";
+    let obj = match json::from_str(doc) {
+        Ok(json::Object(obj)) => obj,
+        _ => fail!("bad test document")
+    };
+    let primitives = match load_scene(&*obj, None, &Path(".")) {
+        Ok(primitives) => primitives,
+        Err(error) => fail!(error)
+    };
+    assert_eq!(primitives.len(), 1);
+    assert_eq!(primitives[0].name, ~"tri");
+    assert_eq!(primitives[0].indices, ~[0u32, 1, 2]);
+    // (1, 0, 0) turns to (0, 0, -1), then moves to (1, 0, -1)
+    let p = &primitives[0].positions[0];
+    assert!((p.x - 1.0).abs() < 0.0001 && p.y.abs() < 0.0001 && (p.z + 1.0).abs() < 0.0001);
+    // (0, 0, 1) turns to (1, 0, 0), then moves to (2, 0, 0)
+    let p = &primitives[0].positions[2];
+    assert!((p.x - 2.0).abs() < 0.0001 && p.z.abs() < 0.0001);
+}
@@ -0,0 +1,161 @@
+use std::f32;
+
+use math::{Vec3, Vec4};
+
+/// Surface properties from an MTL file.
+#[deriving(Clone)]
+pub struct Material {
+    name: ~str,
+    /// `Kd`
+    diffuse: Vec3<f32>,
+    /// `Ks`
+    specular: Vec3<f32>,
+    /// `Ns`, the specular exponent.
+    shininess: f32,
+    /// `d`, or one minus `Tr`.
+    opacity: f32,
+    /// `map_Kd`, resolved against the MTL file's directory.
+    diffuse_map: Option<~str>,
+}
+
+impl Material {
+    /// The MTL defaults, which is also what faces without `usemtl` get.
+    pub fn new(name: &str) -> Material {
+        Material {
+            name: name.to_owned(),
+            diffuse: Vec3::new(0.8f32, 0.8, 0.8),
+            specular: Vec3::new(0.0f32, 0.0, 0.0),
+            shininess: 0.0,
+            opacity: 1.0,
+            diffuse_map: None,
+        }
+    }
+    
+    /// The vertex color for surfaces made of this.
+    pub fn color(&self) -> Vec4<f32> {
+        Vec4::new(self.diffuse.x, self.diffuse.y, self.diffuse.z, self.opacity)
+    }
+}
+
+/// Reads the materials in an MTL file, naming it `source` in errors.
+/// Texture paths are taken relative to `dir`. Statements the renderer has
+/// no use for are skipped.
+pub fn parse(reader: @Reader, source: &str, dir: &Path) -> Result<~[Material], ~str> {
+    let mut materials: ~[Material] = ~[];
+    let mut line_num = 0u;
+    let mut error = None;
+    for reader.each_line |line| {
+        line_num += 1;
+        match parse_line(&mut materials, line, dir) {
+            Ok(()) => {},
+            Err(message) => {
+                error = Some(fmt!("%s:%u: %s", source, line_num, message));
+                break;
+            }
+        }
+    }
+    match error {
+        Some(error) => Err(error),
+        None => Ok(materials)
+    }
+}
+
+fn parse_line(materials: &mut ~[Material], line: &str, dir: &Path) -> Result<(), ~str> {
+    let line = match line.find('#') {
+        Some(i) => line.slice_to(i),
+        None => line
+    };
+    let words: ~[&str] = line.word_iter().collect();
+    if words.len() == 0 {
+        return Ok(());
+    }
+    if words[0] == "newmtl" {
+        if words.len() < 2 {
+            return Err(~"newmtl needs a name");
+        }
+        materials.push(Material::new(words.tail().connect(" ")));
+        return Ok(());
+    }
+    if materials.len() == 0 {
+        return Err(fmt!("%s before any newmtl", words[0]));
+    }
+    let last = materials.len() - 1;
+    let material = &mut materials[last];
+    let args = words.tail();
+
+    match words[0] {
+        "Kd" => match parse_color(args) {
+            Ok(color) => material.diffuse = color,
+            Err(error) => return Err(error)
+        },
+        "Ks" => match parse_color(args) {
+            Ok(color) => material.specular = color,
+            Err(error) => return Err(error)
+        },
+        "Ns" => match parse_number(args) {
+            Ok(value) => material.shininess = value,
+            Err(error) => return Err(error)
+        },
+        "d" => match parse_number(args) {
+            Ok(value) => material.opacity = value.max(&0.0).min(&1.0),
+            Err(error) => return Err(error)
+        },
+        "Tr" => match parse_number(args) {
+            Ok(value) => material.opacity = 1.0 - value.max(&0.0).min(&1.0),
+            Err(error) => return Err(error)
+        },
+        "map_Kd" => {
+            // Options like `-s 1 1 1` come first; the file name is last
+            if args.len() == 0 {
+                return Err(~"map_Kd needs a file name");
+            }
+            material.diffuse_map = Some(dir.push(args[args.len() - 1]).to_str());
+        },
+        _ => {}
+    }
+    Ok(())
+}
+
+fn parse_number(args: &[&str]) -> Result<f32, ~str> {
+    if args.len() != 1 {
+        return Err(~"expected a number");
+    }
+    match f32::from_str(args[0]) {
+        Some(value) => Ok(value),
+        None => Err(fmt!("\"%s\" isn't a number", args[0]))
+    }
+}
+
+fn parse_color(args: &[&str]) -> Result<Vec3<f32>, ~str> {
+    if args.len() == 0 || args[0] == "spectral" || args[0] == "xyz" {
+        return Err(~"only RGB colors are supported");
+    }
+    let mut values = ~[];
+    for args.iter().advance |&word| {
+        match f32::from_str(word) {
+            Some(value) => values.push(value),
+            None => return Err(fmt!("\"%s\" isn't a number", word))
+        }
+    }
+    match values.len() {
+        // A single value is gray
+        1 => Ok(Vec3::new(values[0], values[0], values[0])),
+        3 => Ok(Vec3::new(values[0], values[1], values[2])),
+        _ => Err(~"expected one or three numbers")
+    }
+}
+
+#[test]
+fn test_parse_mtl() {
+    let text = "# from blender\nnewmtl Wood\nKd 0.6 0.4 0.2\nKs 0.5\nNs 96.0\nd 0.75\nmap_Kd -s 2 2 1 wood.tga\n\nnewmtl Plain\n";
+    let materials = match do std::io::with_str_reader(text) |reader| { parse(reader, "test.mtl", &Path("models")) } {
+        Ok(materials) => materials,
+        Err(error) => fail!(error)
+    };
+    assert_eq!(materials.len(), 2);
+    assert_eq!(materials[0].name, ~"Wood");
+    assert_eq!(materials[0].specular.y, 0.5);
+    assert_eq!(materials[0].opacity, 0.75);
+    assert_eq!(materials[0].diffuse_map, Some(~"models/wood.tga"));
+    assert_eq!(materials[1].diffuse.x, 0.8);
+}
@@ -3,4 +3,9 @@ pub use self::obj::Mesh;
 
 mod shader;
 mod obj;
-mod camera;
\ No newline at end of file
+mod camera;
+mod text;
+mod wavefront;
+mod material;
+mod texture;
+mod gltf;
\ No newline at end of file
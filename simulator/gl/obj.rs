@@ -1,12 +1,20 @@
 
 use std::comm;
-use std::{f32, u16};
+use std::{u16, uint};
 use std::io;
 use std::hashmap::HashMap;
 use std::vec;
+use extra::{sort, time};
 
-use math::{Mat4, Mat3, Vec4, Vec3};
-use gl::shader::{AttribLocation, UniformLocation};
+use math::{Mat4, Mat3, Vec4, Vec3, Aabb};
+use gl::shader::{Attribs, Uniforms};
+use gl::wavefront;
+use gl::gltf;
+use gl::material;
+use gl::material::Material;
+use gl::texture;
+use gl::texture::Texture;
+use led::LedResponse;
 
 use es = opengles::gl2;
 
@@ -14,84 +22,195 @@ use es = opengles::gl2;
 #[path = "../check.rs"]
 mod check;
 
+/// Radius of the rods in scene units.
+pub static ROD_RADIUS: f32 = 0.02;
+
+/// How many past colors each rod remembers for the inspector.
+static HISTORY_LEN: uint = 8;
+
+/// Rods are cut into this many slabs so their color can vary along them.
+static ROD_SEGMENTS: uint = 16;
+
+/// Faces meeting at more than this many radians (45 degrees) get a hard
+/// edge between them instead of a smoothed one.
+pub static CREASE_ANGLE: f32 = 0.7853982;
+
 pub struct Mesh {
     vbo_vertices: es::GLuint,
     vbo_normals: es::GLuint,
     vbo_colors: es::GLuint,
+    vbo_texcoords: es::GLuint,
     ibo_elements: es::GLuint,
     object2world: Mat4<f32>,
     vertices: ~[Vec4<f32>],
     colors: ~[Vec4<f32>],
     normals: ~[Vec3<f32>],
-    elements: ~[es::GLushort],
-    port: Option<comm::Port<(f32, f32, f32)>>,
+    /// Texture coordinates from the model file, if it had any.
+    texcoords: ~[Vec3<f32>],
+    /// Kept 32-bit; `upload` narrows them to 16 bits when they fit.
+    elements: ~[es::GLuint],
+    /// `UNSIGNED_SHORT` or `UNSIGNED_INT`, whichever `upload` chose.
+    index_type: es::GLenum,
+    /// The object or group this came from in a model file.
+    name: Option<~str>,
+    /// What `usemtl` gave it. Its diffuse color and opacity are already in
+    /// `colors`.
+    material: Option<Material>,
+    texture: Option<Texture>,
+    rod: Option<uint>,
+    rgb: (u8, u8, u8),
+    response: LedResponse,
+    /// Per-vertex scale for displayed colors, from the rod's diffusion; empty
+    /// for a uniform color.
+    brightness: ~[f32],
+    history: ~[(float, (u8, u8, u8))],
+    highlighted: bool,
+    port: Option<comm::Port<(u8, u8, u8)>>,
+    /// Whether any color is see-through, kept up to date by `measure` so
+    /// rendering needn't scan the colors every frame.
+    translucent: bool,
+    /// The average vertex, likewise.
+    centroid: Vec3<f32>,
 }
 
 impl Mesh {
-    pub fn load_from_obj(reader: @Reader) -> Mesh {
-        let mut vertices: ~[Vec4<f32>] = ~[];
-        let mut colors: ~[Vec4<f32>] = ~[];
-        let mut normals: ~[Vec3<f32>] = ~[];
-        let mut elements: ~[es::GLushort] = ~[];
-        
-        for reader.each_line |line| {
-            if line.starts_with("v ") {
-                let str_vals: ~[&str] = line.split_iter(' ').collect();
-                let x = f32::from_str_radix(str_vals[1], 10);
-                let y = f32::from_str_radix(str_vals[2], 10);
-                let z = f32::from_str_radix(str_vals[3], 10);
-                
-                match (x, y, z) {
-                    (Some(x), Some(y), Some(z)) => {
-                        let v = Vec4::new(x, y, z, 1.0);
-                        vertices.push(v);
-                    },
-                    _ => fail!("Invalid obj file")
-                }
-            } else if line.starts_with("f ") {
-                let str_vals: ~[&str] = line.split_iter(' ').collect();
-                let a = u16::from_str_radix(str_vals[1], 10);
-                let b = u16::from_str_radix(str_vals[2], 10);
-                let c = u16::from_str_radix(str_vals[3], 10);
-                
-                match (a, b, c) {
-                    (Some(a), Some(b), Some(c)) => {
-                        elements.push(a - 1);
-                        elements.push(b - 1);
-                        elements.push(c - 1);
-                    },
-                    _ => fail!("Invalid obj file")
-                }
-            } else {}
-        }
-        
-        normals.grow(vertices.len(), &Vec3::new(0.0, 0.0, 0.0));
-        colors.grow(vertices.len(), &Vec4::new(0.0, 0.0, 0.0, 1.0));
-        println(fmt!("# of vertices: %?", vertices.len()));
-        println(fmt!("# of elements: %?", elements.len()));
-        let mut i = 0;
-        while i < elements.len() {
-            let (ia, ib, ic) = (elements[i], elements[i+1], elements[i+2]);
-            let normal = (Vec3::from4(vertices[ib]) - Vec3::from4(vertices[ia]))
-                .cross(&(Vec3::from4(vertices[ic]) - Vec3::from4(vertices[ia]))).normalize();
-            normals[ia] = normal;
-            normals[ib] = normal;
-            normals[ic] = normal;
-            i += 3;
-        }
-        
-        Mesh {
+    /// A flat mesh facing +z, as used for overlays.
+    pub fn new(vertices: ~[Vec4<f32>], colors: ~[Vec4<f32>], elements: ~[es::GLuint]) -> Mesh {
+        let mut mesh = Mesh {
             vbo_vertices: 0,
             vbo_colors: 0,
             vbo_normals: 0,
+            vbo_texcoords: 0,
             ibo_elements: 0,
             object2world: Mat4::ident(),
+            normals: vec::from_elem(vertices.len(), Vec3::new(0.0f32, 0.0, 1.0)),
             vertices: vertices,
             colors: colors,
-            normals: normals,
+            texcoords: ~[],
             elements: elements,
+            index_type: es::UNSIGNED_SHORT,
+            name: None,
+            material: None,
+            texture: None,
+            rod: None,
+            rgb: (0, 0, 0),
+            response: LedResponse::new(),
+            brightness: ~[],
+            history: ~[],
+            highlighted: false,
             port: None,
+            translucent: false,
+            centroid: Vec3::new(0.0f32, 0.0, 0.0),
+        };
+        mesh.measure();
+        mesh
+    }
+    
+    /// Reads a whole OBJ file as one mesh, naming it `source` in errors.
+    /// Each group is colored with its material's diffuse color, but
+    /// textures and highlights need `load_objects_from_obj`.
+    pub fn load_from_obj(reader: @Reader, source: &str) -> Result<Mesh, ~str> {
+        let model = match wavefront::parse(reader, source) {
+            Ok(model) => model,
+            Err(error) => return Err(error)
+        };
+        let materials = load_materials(&model, source);
+        let parts = do model.groups.map |group| {
+            (group.triangles.slice(0, group.triangles.len()), find_material(materials, &group.material).color())
+        };
+        match Mesh::from_corners(&model, parts) {
+            Ok(mesh) => Ok(mesh),
+            Err(error) => Err(fmt!("%s: %s", source, error))
+        }
+    }
+    
+    /// Reads each object or group in an OBJ file as its own mesh, named
+    /// after it and with its material. MTL files and textures are looked
+    /// for next to `source`.
+    pub fn load_objects_from_obj(reader: @Reader, source: &str) -> Result<~[Mesh], ~str> {
+        let model = match wavefront::parse(reader, source) {
+            Ok(model) => model,
+            Err(error) => return Err(error)
+        };
+        let materials = load_materials(&model, source);
+        let mut meshes = ~[];
+        for model.groups.iter().advance |group| {
+            let material = find_material(materials, &group.material);
+            let parts = [(group.triangles.slice(0, group.triangles.len()), material.color())];
+            match Mesh::from_corners(&model, parts) {
+                Ok(mut mesh) => {
+                    mesh.name = Some(group.name.clone());
+                    match material.diffuse_map {
+                        // Texture coordinates all default to zero without
+                        // any in the file, so a texture would be one color
+                        Some(ref path) if mesh.texcoords.len() > 0 => match texture::load_image(*path) {
+                            Ok(image) => mesh.texture = Some(Texture::new(image)),
+                            Err(error) => error!(error)
+                        },
+                        _ => {}
+                    }
+                    mesh.material = Some(material);
+                    meshes.push(mesh);
+                },
+                Err(error) => return Err(fmt!("%s: %s: %s", source, group.name, error))
+            }
+        }
+        Ok(meshes)
+    }
+    
+    /// Builds a mesh from triangles of OBJ corners, each part with its own
+    /// color. Corners of a part that share a position, texture coordinate
+    /// and normal become one vertex. Normals come from the file if every
+    /// corner has one, and are smoothed otherwise.
+    fn from_corners(model: &wavefront::Model, parts: &[(&[wavefront::Corner], Vec4<f32>)]) -> Result<Mesh, ~str> {
+        let mut vertices: ~[Vec4<f32>] = ~[];
+        let mut colors: ~[Vec4<f32>] = ~[];
+        let mut normals: ~[Vec3<f32>] = ~[];
+        let mut texcoords: ~[Vec3<f32>] = ~[];
+        let mut all_normals = true;
+        let mut elements: ~[es::GLuint] = ~[];
+        let mut seen: HashMap<(uint, uint, uint, uint), es::GLuint> = HashMap::new();
+        let index_or_none = |index: Option<uint>| match index { Some(i) => i, None => uint::max_value };
+        
+        for parts.iter().enumerate().advance |(part, &(corners, ref color))| {
+            for corners.iter().advance |corner| {
+                let key = (part, corner.position, index_or_none(corner.texcoord), index_or_none(corner.normal));
+                let existing = match seen.find(&key) {
+                    Some(&index) => Some(index),
+                    None => None
+                };
+                let index = match existing {
+                    Some(index) => index,
+                    None => {
+                        let index = vertices.len() as es::GLuint;
+                        vertices.push(model.positions[corner.position].clone());
+                        colors.push(color.clone());
+                        match corner.normal {
+                            Some(n) => normals.push(model.normals[n].normalize()),
+                            None => { normals.push(Vec3::new(0.0f32, 0.0, 0.0)); all_normals = false; }
+                        }
+                        texcoords.push(match corner.texcoord {
+                            Some(t) => model.texcoords[t].clone(),
+                            None => Vec3::new(0.0f32, 0.0, 0.0)
+                        });
+                        seen.insert(key, index);
+                        index
+                    }
+                };
+                elements.push(index);
+            }
         }
+        
+        debug!("%u vertices, %u elements", vertices.len(), elements.len());
+        let mut mesh = Mesh::new(vertices, colors, elements);
+        mesh.normals = normals;
+        if model.texcoords.len() > 0 {
+            mesh.texcoords = texcoords;
+        }
+        if !all_normals {
+            mesh.smooth_normals(CREASE_ANGLE);
+        }
+        Ok(mesh)
     }
     
     pub fn gen_base() -> Mesh {
@@ -99,7 +218,7 @@ impl Mesh {
                          Vec4::new(-0.25f32, -0.26, 0.25, 1.0), Vec4::new(-0.25, -0.26, -0.25, 1.0),
                          Vec4::new(0.25, 0.0, -0.25, 1.0), Vec4::new(0.25, 0.0, 0.25, 1.0),
                          Vec4::new(-0.25f32, 0.0, 0.25, 1.0), Vec4::new(-0.25, 0.0, -0.25, 1.0),];
-        let mut colors = ~[];
+        let colors = vec::from_elem(vertices.len(), Vec4::new(0.0f32, 0.0, 0.0, 1.0));
         let elements = ~[0, 1, 2,
                          4, 7, 5,
                          0, 4, 1,
@@ -112,53 +231,249 @@ impl Mesh {
                          6, 7, 3,
                          7, 4, 3,
                          4, 5, 1,];
-        let mut normals = ~[];
         
-        normals.grow(vertices.len(), &Vec3::new(0.0f32, 0.0, 0.0));
-        colors.grow(vertices.len(), &Vec4::new(0.0f32, 0.0, 0.0, 1.0));
-        println(fmt!("# of vertices: %?", vertices.len()));
-        println(fmt!("# of elements: %?", elements.len()));
+        debug!("%u vertices, %u elements", vertices.len(), elements.len());
+        let mut base = Mesh::new(vertices, colors, elements);
+        base.smooth_normals(CREASE_ANGLE);
+        base
+    }
+    
+    /// Recomputes every normal as the area-weighted average of the faces
+    /// around its position, leaving out faces that meet the corner's own at
+    /// more than `crease_angle` radians. Vertices on hard edges are split so
+    /// each side keeps its own normal.
+    pub fn smooth_normals(&mut self, crease_angle: f32) {
+        // A face's cross product is twice its area long, which is just the
+        // weighting wanted
+        let mut weighted = ~[];
+        let mut facing = ~[];
         let mut i = 0;
-        while i < elements.len() {
-            let (ia, ib, ic) = (elements[i], elements[i+1], elements[i+2]);
-            let normal = (Vec3::from4(vertices[ib]) - Vec3::from4(vertices[ia]))
-                .cross(&(Vec3::from4(vertices[ic]) - Vec3::from4(vertices[ia]))).normalize();
-            normals[ia] = normal;
-            normals[ib] = normal;
-            normals[ic] = normal;
+        while i < self.elements.len() {
+            let (a, b, c) = (Vec3::from4(self.vertices[self.elements[i]].clone()),
+                             Vec3::from4(self.vertices[self.elements[i+1]].clone()),
+                             Vec3::from4(self.vertices[self.elements[i+2]].clone()));
+            let cross = (b - a).cross(&(c - a));
+            facing.push(if cross.dot(&cross) > 0.0 { cross.normalize() } else { cross.clone() });
+            weighted.push(cross);
             i += 3;
         }
         
-        Mesh {
-            vbo_vertices: 0,
-            vbo_colors: 0,
-            vbo_normals: 0,
-            ibo_elements: 0,
-            object2world: Mat4::ident(),
-            vertices: vertices,
-            colors: colors,
-            normals: normals,
-            elements: elements,
-            port: None
+        // Vertices in the same spot smooth together even when the model
+        // splits them for texture coordinates
+        let (position_ids, position_count) = self.position_ids();
+        let mut around: ~[~[uint]] = vec::from_elem(position_count, ~[]);
+        for self.elements.iter().enumerate().advance |(i, &v)| {
+            around[position_ids[v]].push(i / 3);
         }
+        
+        let min_cos = crease_angle.cos();
+        let mut vertices = ~[];
+        let mut colors = ~[];
+        let mut texcoords = ~[];
+        let mut normals = ~[];
+        let mut elements = ~[];
+        let mut seen: HashMap<(uint, int, int, int), es::GLuint> = HashMap::new();
+        for self.elements.iter().enumerate().advance |(i, &v)| {
+            let face = i / 3;
+            let mut sum = Vec3::new(0.0f32, 0.0, 0.0);
+            for around[position_ids[v]].iter().advance |&other| {
+                if facing[face].dot(&facing[other]) >= min_cos {
+                    sum = sum + weighted[other];
+                }
+            }
+            let normal = if sum.dot(&sum) > 0.0 { sum.normalize() } else { facing[face].clone() };
+            
+            // Corners of one vertex that end up with the same normal share it
+            let key = (v as uint, (normal.x * 10000.0) as int, (normal.y * 10000.0) as int,
+                       (normal.z * 10000.0) as int);
+            let existing = match seen.find(&key) {
+                Some(&index) => Some(index),
+                None => None
+            };
+            let index = match existing {
+                Some(index) => index,
+                None => {
+                    let index = vertices.len() as es::GLuint;
+                    vertices.push(self.vertices[v].clone());
+                    colors.push(self.colors[v].clone());
+                    if self.texcoords.len() > 0 {
+                        texcoords.push(self.texcoords[v].clone());
+                    }
+                    normals.push(normal);
+                    seen.insert(key, index);
+                    index
+                }
+            };
+            elements.push(index);
+        }
+        
+        self.vertices = vertices;
+        self.colors = colors;
+        self.texcoords = texcoords;
+        self.normals = normals;
+        self.elements = elements;
+        self.measure();
     }
     
-    pub fn better_rod(x: f32, y: f32, height: f32, port: comm::Port<(f32, f32, f32)>) -> Mesh {
-        let mut basic = Mesh::load_from_obj_file("cylinder.obj");
+    /// Numbers each distinct vertex position, returning each vertex's
+    /// number and how many there are.
+    fn position_ids(&self) -> (~[uint], uint) {
+        let count = self.vertices.len();
+        let same = |a: uint, b: uint| {
+            let (p, q) = (&self.vertices[a], &self.vertices[b]);
+            p.x == q.x && p.y == q.y && p.z == q.z
+        };
+        let mut order = vec::from_fn(count, |i| i);
+        sort::quick_sort(order, |&a, &b| {
+            let (p, q) = (&self.vertices[a], &self.vertices[b]);
+            p.x < q.x || (p.x == q.x && (p.y < q.y || (p.y == q.y && p.z <= q.z)))
+        });
+        let mut ids = vec::from_elem(count, 0u);
+        let mut next = 0u;
+        for uint::range(0, count) |i| {
+            if i > 0 && !same(order[i - 1], order[i]) {
+                next += 1;
+            }
+            ids[order[i]] = next;
+        }
+        (ids, if count > 0 { next + 1 } else { 0 })
+    }
+    
+    /// A rod cut from `unit`, a cylinder of unit radius and height standing
+    /// on the origin, whose colors go through `response` on their way to
+    /// the screen. `profile` gives the relative light at each fraction of
+    /// the way up, from the LED at the bottom to the top end.
+    pub fn better_rod(unit: &Mesh, index: uint, x: f32, y: f32, height: f32, response: LedResponse,
+                      profile: &fn(f32) -> f32) -> Mesh {
+        let mut basic = unit.slice(ROD_SEGMENTS);
+        basic.brightness = do basic.vertices.map |v| { response.encode(profile(v.y)) };
         basic.vertices = do basic.vertices.map |v| {
-            Vec4::new(v.x * 0.02 + x, v.y * height, v.z * 0.02 + y, v.w)
+            Vec4::new(v.x * ROD_RADIUS + x, v.y * height, v.z * ROD_RADIUS + y, v.w)
         };
-        basic.colors = vec::from_elem(basic.vertices.len(), Vec4::new(1.0f32, 0.0, 0.0, 0.6));
-        basic.port = Some(port);
+        basic.rod = Some(index);
+        basic.rgb = (255, 0, 0);
+        basic.response = response;
+        basic.paint();
+        basic.measure();
         basic
     }
     
+    /// Cuts a unit-height mesh into `segments` slabs along y, so per-vertex
+    /// colors can vary along it. Vertices are no longer shared afterwards.
+    fn slice(&self, segments: uint) -> Mesh {
+        let mut vertices = ~[];
+        let mut normals = ~[];
+        let mut elements: ~[es::GLuint] = ~[];
+        let mut i = 0;
+        while i < self.elements.len() {
+            let triangle = do self.elements.slice(i, i + 3).map |&e| {
+                (self.vertices[e].clone(), self.normals[e].clone())
+            };
+            for uint::range(0, segments) |k| {
+                let low = (k as f32) / (segments as f32);
+                let high = ((k + 1) as f32) / (segments as f32);
+                let piece = clip_y(clip_y(triangle, low, true), high, false);
+                if piece.len() < 3 {
+                    loop;
+                }
+                let first = vertices.len();
+                for piece.iter().advance |&(ref v, ref n)| {
+                    vertices.push(v.clone());
+                    normals.push(n.clone());
+                }
+                for uint::range(1, piece.len() - 1) |j| {
+                    elements.push_all([first as es::GLuint, (first + j) as es::GLuint,
+                                       (first + j + 1) as es::GLuint]);
+                }
+            }
+            i += 3;
+        }
+        
+        let colors = vec::from_elem(vertices.len(), Vec4::new(0.0f32, 0.0, 0.0, 1.0));
+        let mut mesh = Mesh::new(vertices, colors, elements);
+        mesh.normals = normals;
+        mesh
+    }
+    
+    /// Colors the mesh from whatever arrives on `port`, checked every draw.
+    pub fn listen(&mut self, port: comm::Port<(u8, u8, u8)>) {
+        self.port = Some(port);
+    }
+    
+    pub fn set_rgb(&mut self, r: u8, g: u8, b: u8) {
+        self.rgb = (r, g, b);
+        self.paint();
+        if self.history.len() == HISTORY_LEN {
+            self.history.shift();
+        }
+        self.history.push((time::precise_time_s(), (r, g, b)));
+        if self.uploaded() {
+            self.reload_colors();
+        }
+    }
+    
+    /// Recomputes the vertex colors from `rgb`.
+    fn paint(&mut self) {
+        let (r, g, b) = self.rgb;
+        let (r, g, b) = self.response.to_display(r, g, b);
+        self.colors = if self.brightness.len() == self.vertices.len() {
+            do self.brightness.map |&s| {
+                Vec4::new((r * s).min(&1.0), (g * s).min(&1.0), (b * s).min(&1.0), 0.6)
+            }
+        } else {
+            vec::from_elem(self.vertices.len(), Vec4::new(r, g, b, 0.6))
+        };
+        self.translucent = self.colors.iter().any_(|color| color.w < 1.0);
+    }
+    
+    #[inline]
+    pub fn load_from_obj_file(path_str: &str) -> Result<Mesh, ~str> {
+        match io::file_reader(&Path(path_str)) {
+            Ok(reader) => Mesh::load_from_obj(reader, path_str),
+            Err(error) => Err(fmt!("%s: %s", path_str, error))
+        }
+    }
+    
     #[inline]
-    pub fn load_from_obj_file(path_str: &str) -> Mesh {
-        Mesh::load_from_obj(match io::file_reader(&Path(path_str)) {
-            Ok(reader) => reader,
-            Err(error) => fail!(error)
-        })
+    pub fn load_objects_from_obj_file(path_str: &str) -> Result<~[Mesh], ~str> {
+        match io::file_reader(&Path(path_str)) {
+            Ok(reader) => Mesh::load_objects_from_obj(reader, path_str),
+            Err(error) => Err(fmt!("%s: %s", path_str, error))
+        }
+    }
+    
+    /// Reads the default scene of a glTF or GLB file, one mesh per
+    /// primitive, named after its node.
+    pub fn load_from_gltf_file(path_str: &str) -> Result<~[Mesh], ~str> {
+        let primitives = match gltf::load(path_str) {
+            Ok(primitives) => primitives,
+            Err(error) => return Err(error)
+        };
+        let mut meshes = ~[];
+        for primitives.consume_iter().advance |primitive| {
+            let gltf::Primitive { name, positions, normals, texcoords, colors, indices } = primitive;
+            let has_normals = normals.len() > 0;
+            let mut mesh = Mesh::new(positions, colors, indices);
+            mesh.name = Some(name);
+            mesh.texcoords = texcoords;
+            if has_normals {
+                mesh.normals = normals;
+            } else {
+                mesh.smooth_normals(CREASE_ANGLE);
+            }
+            meshes.push(mesh);
+        }
+        Ok(meshes)
+    }
+    
+    /// Reads a model as named meshes, as glTF for `.gltf` and `.glb` files
+    /// and OBJ otherwise.
+    pub fn load_model_file(path_str: &str) -> Result<~[Mesh], ~str> {
+        if [".gltf", ".GLTF", ".glb", ".GLB"].iter().any_(|ext| path_str.ends_with(*ext)) {
+            Mesh::load_from_gltf_file(path_str)
+        } else {
+            Mesh::load_objects_from_obj_file(path_str)
+        }
     }
     
     pub fn upload(&mut self) {
@@ -180,10 +495,25 @@ impl Mesh {
             check!(es::buffer_data(es::ARRAY_BUFFER, self.normals, es::STATIC_DRAW));
         }
         
+        if self.texcoords.len() > 0 {
+            self.vbo_texcoords = check!(es::gen_buffers(1)[0]);
+            check!(es::bind_buffer(es::ARRAY_BUFFER, self.vbo_texcoords));
+            check!(es::buffer_data(es::ARRAY_BUFFER, self.texcoords, es::STATIC_DRAW));
+        }
+        
         if self.elements.len() > 0 {
             self.ibo_elements = check!(es::gen_buffers(1)[0]);
             check!(es::bind_buffer(es::ELEMENT_ARRAY_BUFFER, self.ibo_elements));
-            check!(es::buffer_data(es::ELEMENT_ARRAY_BUFFER, self.elements, es::STATIC_DRAW));
+            // Most meshes are small enough for 16-bit indices, which take
+            // half the memory
+            if self.vertices.len() <= u16::max_value as uint + 1 {
+                let elements = do self.elements.map |&e| { e as es::GLushort };
+                check!(es::buffer_data(es::ELEMENT_ARRAY_BUFFER, elements, es::STATIC_DRAW));
+                self.index_type = es::UNSIGNED_SHORT;
+            } else {
+                check!(es::buffer_data(es::ELEMENT_ARRAY_BUFFER, self.elements, es::STATIC_DRAW));
+                self.index_type = es::UNSIGNED_INT;
+            }
         }
     }
     
@@ -194,41 +524,96 @@ impl Mesh {
         }
     }
     
+    /// Picks up the latest color from the port, if there is one.
+    pub fn poll(&mut self) {
+        let received = match self.port {
+            Some(ref port) if port.peek() => Some(port.recv()),
+            _ => None
+        };
+        match received {
+            Some((r, g, b)) => self.set_rgb(r, g, b),
+            None => {}
+        }
+    }
+    
+    pub fn is_translucent(&self) -> bool {
+        self.translucent
+    }
+    
+    pub fn center(&self) -> Vec3<f32> {
+        self.centroid.clone()
+    }
+    
+    /// Works out `translucent` and `centroid` again, after the colors or
+    /// vertices have been replaced.
+    fn measure(&mut self) {
+        self.translucent = self.colors.iter().any_(|color| color.w < 1.0);
+        let mut sum = Vec3::new(0.0f32, 0.0, 0.0);
+        for self.vertices.iter().advance |v| {
+            sum = sum + Vec3::from4(v.clone());
+        }
+        self.centroid = sum * (1.0 / (self.vertices.len().max(&1) as f32));
+    }
+    
+    /// The box around the mesh where it sits in the scene.
+    pub fn bounds(&self) -> Option<Aabb> {
+        let placed = do self.vertices.map |v| { Vec3::from4(self.object2world.transform(v)) };
+        Aabb::from_points(placed)
+    }
+    
     pub fn translate(&mut self, translation: Vec3<f32>) {
         self.object2world = self.object2world.translate(translation);
     }
     
-    pub fn draw(&mut self, model:Mat4<f32>, attribs: &HashMap<~str, AttribLocation>, uniforms: &HashMap<~str, UniformLocation>) {
+    /// Scales the vertices themselves, say from a model's units to the
+    /// scene's. Only meaningful before `upload`.
+    pub fn scale(&mut self, factor: f32) {
+        self.vertices = do self.vertices.map |v| {
+            Vec4::new(v.x * factor, v.y * factor, v.z * factor, v.w)
+        };
+        self.centroid = self.centroid * factor;
+    }
+    
+    pub fn draw(&mut self, model:Mat4<f32>, attribs: &Attribs, uniforms: &Uniforms) {
         if !self.uploaded() {
             fail!("Hey! You haven't uploaded this mesh yet!'");
         }
         
-        match self.port {
-            Some(ref port) => {
-                if port.peek() {
-                    let (r, g, b) = port.recv();
-                    self.colors = vec::from_elem(self.colors.len(), Vec4::new(r, g, b, 0.6));
-                    self.reload_colors();
-                }
-            },
-            None => {}
+        attribs.coord.update_f32(self.vbo_vertices, 4);
+        attribs.normal.update_f32(self.vbo_normals, 3);
+        attribs.color.update_f32(self.vbo_colors, 4);
+        if self.vbo_texcoords != 0 {
+            attribs.texcoord.update_f32(self.vbo_texcoords, 3);
+        } else {
+            attribs.texcoord.disable();
         }
         
-        attribs.find_equiv(&("v_coord")).get().update_f32(self.vbo_vertices, 4);
-        attribs.find_equiv(&("v_normal")).get().update_f32(self.vbo_normals, 3);
-        attribs.find_equiv(&("v_color")).get().update_f32(self.vbo_colors, 4);
-        
         //let angle = (glfw::get_time() * 3.14159 / 4.0) as f32;
         //let my_model = self.object2world.rotate(angle, Vec3::new(0.0f32, 1.0, 0.0));
         let final_model = model /* * my_model */;
         let m_inv_transp = Mat3::from_four(final_model).trans_inv();
         
-        uniforms.find_equiv(&("m_orig")).get().update_mat4_f32(final_model);
-        uniforms.find_equiv(&("m")).get().update_mat4_f32(final_model);
-        uniforms.find_equiv(&("m_inv_transp")).get().update_mat3_f32(m_inv_transp);
+        uniforms.model_orig.set(final_model);
+        uniforms.model.set(final_model);
+        uniforms.model_inv_transp.set(m_inv_transp);
+        uniforms.highlight.set(if self.highlighted { 1.0 } else { 0.0 });
+        let (specular, shininess) = match self.material {
+            Some(ref material) => (material.specular.clone(), material.shininess),
+            None => (Vec3::new(0.0f32, 0.0, 0.0), 0.0)
+        };
+        uniforms.specular.set(specular);
+        uniforms.shininess.set(shininess);
+        match self.texture {
+            Some(ref mut texture) => {
+                texture.bind();
+                uniforms.diffuse_map.set(0);
+                uniforms.textured.set(1.0);
+            },
+            None => uniforms.textured.set(0.0)
+        }
         
         check!(es::bind_buffer(es::ELEMENT_ARRAY_BUFFER, self.ibo_elements));
-        check!(es::draw_elements(es::TRIANGLES, self.elements.len() as es::GLint, es::UNSIGNED_SHORT, None));
+        check!(es::draw_elements(es::TRIANGLES, self.elements.len() as es::GLint, self.index_type, None));
     }
     
     #[inline]
@@ -237,8 +622,123 @@ impl Mesh {
     }
 }
 
+/// Keeps the part of a polygon above the plane at height `y`, or below it if
+/// `above` is false, interpolating normals along cut edges.
+fn clip_y(polygon: &[(Vec4<f32>, Vec3<f32>)], y: f32, above: bool) -> ~[(Vec4<f32>, Vec3<f32>)] {
+    let inside = |v: &Vec4<f32>| if above { v.y >= y } else { v.y <= y };
+    let mut clipped = ~[];
+    for uint::range(0, polygon.len()) |i| {
+        let (ref a, ref na) = polygon[i];
+        let (ref b, ref nb) = polygon[(i + 1) % polygon.len()];
+        if inside(a) {
+            clipped.push((a.clone(), na.clone()));
+        }
+        if inside(a) != inside(b) {
+            let t = (y - a.y) / (b.y - a.y);
+            clipped.push((*a + (*b - *a) * t, (*na + (*nb - *na) * t).normalize()));
+        }
+    }
+    clipped
+}
+
+/// Reads the MTL files an OBJ file names, from next to it. A missing or
+/// broken library only costs its materials, since models often come
+/// without theirs.
+fn load_materials(model: &wavefront::Model, source: &str) -> ~[Material] {
+    let dir = Path(source).dir_path();
+    let mut materials = ~[];
+    for model.libraries.iter().advance |library| {
+        let path = dir.push(*library);
+        let path_str = path.to_str();
+        let result = match io::file_reader(&path) {
+            Ok(reader) => material::parse(reader, path_str, &dir),
+            Err(error) => Err(fmt!("%s: %s", path_str, error))
+        };
+        match result {
+            Ok(found) => materials.push_all_move(found),
+            Err(error) => warn!(error)
+        }
+    }
+    materials
+}
+
+/// The material named by `usemtl`, or the MTL defaults.
+fn find_material(materials: &[Material], name: &Option<~str>) -> Material {
+    match *name {
+        Some(ref name) => match materials.iter().find_(|material| material.name == *name) {
+            Some(material) => material.clone(),
+            None => {
+                warn!(fmt!("material %s not found", *name));
+                Material::new(*name)
+            }
+        },
+        None => Material::new("default")
+    }
+}
+
 impl Drop for Mesh {
     fn drop(&self) {
-        es::delete_buffers([self.vbo_vertices, self.vbo_normals, self.ibo_elements]);
+        // Meshes built for export never see a GL context
+        let buffers = [self.vbo_vertices, self.vbo_normals, self.vbo_colors, self.vbo_texcoords,
+                       self.ibo_elements];
+        if buffers.iter().any_(|&buffer| buffer != 0) {
+            es::delete_buffers(buffers);
+        }
     }
-}
\ No newline at end of file
+}
+
+/// A white mesh through `points`, for tests to build their shapes from.
+#[cfg(test)]
+pub fn test_mesh(points: &[(f32, f32, f32)], elements: ~[es::GLuint]) -> Mesh {
+    let vertices = do points.map |&(x, y, z)| { Vec4::new(x, y, z, 1.0f32) };
+    let colors = vec::from_elem(vertices.len(), Vec4::new(1.0f32, 1.0, 1.0, 1.0));
+    Mesh::new(vertices, colors, elements)
+}
+
+#[cfg(test)]
+fn hinge(h: f32) -> Mesh {
+    // Two triangles meeting along the x axis, one flat and one whose normal
+    // is atan(h) from it, with the tilted one the bigger of the two
+    let mut mesh = test_mesh([(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, -1.0, h)],
+                             ~[0, 1, 2, 1, 0, 3]);
+    mesh.smooth_normals(CREASE_ANGLE);
+    mesh
+}
+
+#[cfg(test)]
+fn assert_normal(mesh: &Mesh, corner: uint, expected: Vec3<f32>) {
+    let (n, e) = (&mesh.normals[mesh.elements[corner]], expected.normalize());
+    assert!((n.x - e.x).abs() < 1e-4 && (n.y - e.y).abs() < 1e-4 && (n.z - e.z).abs() < 1e-4,
+            fmt!("corner %u: %? != %?", corner, n, e));
+}
+
+#[test]
+fn test_smooth_normals() {
+    // 30 degrees apart, within the crease angle: the shared corners get
+    // the faces' cross products summed, so the bigger face counts for more
+    let h = 0.57735f32;
+    let mesh = hinge(h);
+    assert_eq!(mesh.vertices.len(), 4);
+    assert_eq!(mesh.elements[0], mesh.elements[4]);
+    assert_eq!(mesh.elements[1], mesh.elements[3]);
+    assert_normal(&mesh, 0, Vec3::new(0.0, h, 2.0));
+    assert_normal(&mesh, 1, Vec3::new(0.0, h, 2.0));
+    assert_normal(&mesh, 2, Vec3::new(0.0, 0.0, 1.0));
+    assert_normal(&mesh, 5, Vec3::new(0.0, h, 1.0));
+}
+
+#[test]
+fn test_hard_edges_split() {
+    // 90 degrees apart: each face keeps its own normal, so the shared
+    // corners become two vertices each
+    let mesh = hinge(1e6);
+    assert_eq!(mesh.vertices.len(), 6);
+    assert!(mesh.elements[0] != mesh.elements[4]);
+    assert!(mesh.elements[1] != mesh.elements[3]);
+    for [0u, 1, 2].iter().advance |&corner| {
+        assert_normal(&mesh, corner, Vec3::new(0.0, 0.0, 1.0));
+    }
+    for [3u, 4, 5].iter().advance |&corner| {
+        assert_normal(&mesh, corner, Vec3::new(0.0, 1.0, 0.0));
+    }
+}
@@ -1,8 +1,9 @@
 
-use std::io;
+use std::{io, char};
+use std::to_str::ToStr;
 use es = opengles::gl2;
 
-use math::{Mat4, Mat3};
+use math::{Mat4, Mat3, Vec3};
 
 #[macro_escape]
 #[path = "../check.rs"]
@@ -23,82 +24,461 @@ impl AttribLocation {
     }
     
     pub fn update_f32(&self, buf: u32, size: es::GLint) {
+        // Shaders that don't use an attribute don't have it at all
+        if self.raw == -1 {
+            return;
+        }
         self.enable_vertex_attrib_array();
         check!(es::bind_buffer(es::ARRAY_BUFFER, buf));
         self.vertex_attrib_pointer_f32(size, false, 0, 0);
     }
+    
+    /// For meshes without the attribute; the shader then sees (0, 0, 0, 1).
+    pub fn disable(&self) {
+        if self.raw == -1 {
+            return;
+        }
+        check!(es::disable_vertex_attrib_array(self.raw as es::GLuint));
+    }
 }
 
-impl UniformLocation {
-    pub fn update_f32(&self, val: f32) {
-        check!(es::uniform_1f(self.raw, val));
+// Uniforms by their GLSL type, so each can only be set to a value of that
+// type. Setting one the program lacks does nothing, as GL ignores
+// location -1.
+pub struct FloatUniform {loc: UniformLocation}
+pub struct Vec3Uniform {loc: UniformLocation}
+pub struct Mat3Uniform {loc: UniformLocation}
+pub struct Mat4Uniform {loc: UniformLocation}
+/// A `sampler2D`, set to a texture unit.
+pub struct SamplerUniform {loc: UniformLocation}
+
+impl FloatUniform {
+    pub fn set(&self, val: f32) {
+        check!(es::uniform_1f(self.loc.raw, val));
     }
-    pub fn update_mat4_f32(&self, mat: Mat4<f32>) {
-        check!(es::uniform_matrix_4fv(self.raw, false, *mat.to_flat()));
+}
+
+impl Vec3Uniform {
+    pub fn set(&self, vec: Vec3<f32>) {
+        check!(es::uniform_3f(self.loc.raw, vec.x, vec.y, vec.z));
     }
-    pub fn update_mat3_f32(&self, mat: Mat3<f32>) {
-        check!(es::uniform_matrix_3fv(self.raw, false, *mat.to_flat()));
+}
+
+impl Mat3Uniform {
+    pub fn set(&self, mat: Mat3<f32>) {
+        check!(es::uniform_matrix_3fv(self.loc.raw, false, *mat.to_flat()));
+    }
+}
+
+impl Mat4Uniform {
+    pub fn set(&self, mat: Mat4<f32>) {
+        check!(es::uniform_matrix_4fv(self.loc.raw, false, *mat.to_flat()));
+    }
+}
+
+impl SamplerUniform {
+    pub fn set(&self, unit: i32) {
+        check!(es::uniform_1i(self.loc.raw, unit));
     }
 }
 
+#[deriving(Eq, Clone)]
+pub enum Stage {
+    VertexStage,
+    FragmentStage,
+}
+
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            VertexStage => "vertex",
+            FragmentStage => "fragment",
+        }
+    }
+    
+    fn gl_type(&self) -> es::GLuint {
+        match *self {
+            VertexStage => es::VERTEX_SHADER,
+            FragmentStage => es::FRAGMENT_SHADER,
+        }
+    }
+}
+
+/// Why a shader couldn't be used.
+pub enum ShaderError {
+    /// A source file couldn't be read: the path and why.
+    ReadError(~str, ~str),
+    /// A stage didn't compile: the file, the stage, the first line the info
+    /// log blames if it could be found, and the log itself.
+    CompileError(~str, Stage, Option<uint>, ~str),
+    /// Both stages compiled but didn't link: the two files and the log.
+    LinkError(~str, ~str, ~str),
+    /// The program lacks inputs it's required to use: its name and the
+    /// missing attributes and uniforms.
+    MissingInputs(~str, ~[~str]),
+}
+
+impl ToStr for ShaderError {
+    fn to_str(&self) -> ~str {
+        match *self {
+            ReadError(ref path, ref error) => fmt!("%s: %s", *path, *error),
+            CompileError(ref path, stage, Some(line), ref log) =>
+                fmt!("%s:%u: %s shader failed to compile: %s", *path, line, stage.name(), log.trim()),
+            CompileError(ref path, stage, None, ref log) =>
+                fmt!("%s: %s shader failed to compile: %s", *path, stage.name(), log.trim()),
+            LinkError(ref vertex, ref fragment, ref log) =>
+                fmt!("%s + %s: failed to link: %s", *vertex, *fragment, log.trim()),
+            MissingInputs(ref name, ref missing) =>
+                fmt!("%s: missing required inputs %s", *name, missing.connect(", ")),
+        }
+    }
+}
+
+/// The first source line an info log complains about. Drivers disagree on
+/// the format: Mesa writes `0:12(5): error`, AMD `ERROR: 0:12:` and NVIDIA
+/// `0(12) : error`, where the 0 is the source string.
+fn error_line(log: &str) -> Option<uint> {
+    for log.line_iter().advance |line| {
+        let line = line.trim_left();
+        let line = if line.starts_with("ERROR: ") { line.slice_from(7) } else { line };
+        if !line.starts_with("0:") && !line.starts_with("0(") {
+            loop;
+        }
+        let mut number = None;
+        for line.slice_from(2).iter().advance |c| {
+            match char::to_digit(c, 10) {
+                Some(digit) => number = Some(number.get_or_default(0) * 10 + digit),
+                None => break
+            }
+        }
+        if number.is_some() {
+            return number;
+        }
+    }
+    None
+}
+
 pub struct Shader {
     prog: es::GLuint,
     vert_obj: es::GLuint,
     frag_obj: es::GLuint,
 }
 
-fn load_shader(path_str: ~str, shader_type: es::GLuint) -> es::GLuint {
-    let path = Path(path_str);
-    let reader = match io::file_reader(&path) {
-        Ok(reader) => reader,
-        Err(error) => fail!(error)
-    };
-    
-    let shader = check!(es::create_shader(shader_type));
-    check!(es::shader_source(shader, [reader.read_whole_stream()]));
+fn read_source(path_str: &str) -> Result<~[u8], ShaderError> {
+    match io::file_reader(&Path(path_str)) {
+        Ok(reader) => Ok(reader.read_whole_stream()),
+        Err(error) => Err(ReadError(path_str.to_owned(), error))
+    }
+}
+
+/// Compiles one stage, calling it `source_name` in errors.
+fn compile_shader(source_name: &str, source: ~[u8], stage: Stage) -> Result<es::GLuint, ShaderError> {
+    let shader = check!(es::create_shader(stage.gl_type()));
+    check!(es::shader_source(shader, [source]));
     check!(es::compile_shader(shader));
 
     match check!(es::get_shader_iv(shader, es::COMPILE_STATUS)) {
-        0 => fail!(check!(es::get_shader_info_log(shader))),
-        _ => shader
+        0 => {
+            let log = check!(es::get_shader_info_log(shader));
+            check!(es::delete_shader(shader));
+            Err(CompileError(source_name.to_owned(), stage, error_line(log), log))
+        },
+        _ => Ok(shader)
     }
 }
 
 impl Shader {
-    pub fn from_files(vertex_file: &str, fragment_file: &str) -> Shader {
-        let shader_program = check!(es::create_program());
-        let vertex_shader = load_shader(vertex_file.to_owned(), es::VERTEX_SHADER);
-        let fragment_shader = load_shader(fragment_file.to_owned(), es::FRAGMENT_SHADER);
+    pub fn from_files(vertex_file: &str, fragment_file: &str) -> Result<Shader, ShaderError> {
+        match (read_source(vertex_file), read_source(fragment_file)) {
+            (Ok(vertex), Ok(fragment)) => Shader::from_sources(vertex_file, vertex, fragment_file, fragment),
+            (Err(error), _) | (_, Err(error)) => Err(error)
+        }
+    }
+    
+    /// Builds a program from sources in memory, named for errors.
+    pub fn from_sources(vertex_name: &str, vertex_source: ~[u8],
+                        fragment_name: &str, fragment_source: ~[u8]) -> Result<Shader, ShaderError> {
+        let vertex_shader = match compile_shader(vertex_name, vertex_source, VertexStage) {
+            Ok(shader) => shader,
+            Err(error) => return Err(error)
+        };
+        let fragment_shader = match compile_shader(fragment_name, fragment_source, FragmentStage) {
+            Ok(shader) => shader,
+            Err(error) => {
+                check!(es::delete_shader(vertex_shader));
+                return Err(error);
+            }
+        };
         
+        let shader_program = check!(es::create_program());
         check!(es::attach_shader(shader_program, vertex_shader));
         check!(es::attach_shader(shader_program, fragment_shader));
         check!(es::link_program(shader_program));
-        check!(es::use_program(shader_program));
         
-        Shader {
+        // Dropping it cleans up both stages along with the program
+        let shader = Shader {
             prog: shader_program,
             vert_obj: vertex_shader,
             frag_obj: fragment_shader,
+        };
+        match check!(es::get_program_iv(shader_program, es::LINK_STATUS)) {
+            0 => Err(LinkError(vertex_name.to_owned(), fragment_name.to_owned(),
+                               check!(es::get_program_info_log(shader_program)))),
+            _ => Ok(shader)
         }
     }
     
-    pub fn get_attrib_location(&self, name: &str) -> AttribLocation {
-        let loc = check!(es::get_attrib_location(self.prog, name.to_owned()));
-        AttribLocation {
-            raw: match loc {
-                -1 => { error!(fmt!("%s attrib not found", name)); loc },
-                _ => loc
-            }
+    pub fn bind(&self) {
+        check!(es::use_program(self.prog));
+    }
+    
+    /// `None` if the program has no such attribute, which includes ones
+    /// the compiler dropped for being unused.
+    pub fn get_attrib_location(&self, name: &str) -> Option<AttribLocation> {
+        match check!(es::get_attrib_location(self.prog, name.to_owned())) {
+            -1 => None,
+            loc => Some(AttribLocation { raw: loc })
         }
     }
     
-    pub fn get_uniform_location(&self, name: &str) -> UniformLocation {
-        let loc = check!(es::get_uniform_location(self.prog, name.to_owned()));
-        UniformLocation {
-            raw: match loc {
-                -1 => { error!(fmt!("%s uniform not found", name)); loc },
-                _ => loc
+    /// `None` if the program has no such uniform, or doesn't use it.
+    pub fn get_uniform_location(&self, name: &str) -> Option<UniformLocation> {
+        match check!(es::get_uniform_location(self.prog, name.to_owned())) {
+            -1 => None,
+            loc => Some(UniformLocation { raw: loc })
+        }
+    }
+}
+
+impl Drop for Shader {
+    fn drop(&self) {
+        es::delete_program(self.prog);
+        es::delete_shader(self.vert_obj);
+        es::delete_shader(self.frag_obj);
+    }
+}
+
+/// Inputs no shader can draw meshes without.
+static CORE_INPUTS: [&'static str, ..5] = ["v_coord", "v_color", "m", "v", "p"];
+
+/// Every input a program is looked up for, which is what a shader can be
+/// required to use.
+static INPUTS: [&'static str, ..14] = ["v_coord", "v_normal", "v_color", "v_texcoord", "m_orig", "m", "v", "p",
+                                       "m_inv_transp", "highlight", "textured", "diffuse_map", "specular",
+                                       "shininess"];
+
+/// Whether programs are looked up for an input called `name`.
+pub fn is_input(name: &str) -> bool {
+    INPUTS.iter().any_(|&n| n == name)
+}
+
+/// Looks up inputs, noting the required ones the program lacks. Anything
+/// else a shader is free to ignore, in which case the compiler drops it and
+/// setting it does nothing, so a misspelt name only shows up as missing if
+/// the shader is required to use it.
+struct Binder<'self> {
+    shader: &'self Shader,
+    required: &'self [&'static str],
+    missing: ~[~str],
+}
+
+impl<'self> Binder<'self> {
+    fn is_required(&self, name: &str) -> bool {
+        CORE_INPUTS.iter().any_(|&n| n == name) || self.required.iter().any_(|&n| n == name)
+    }
+
+    fn attrib(&mut self, name: &str) -> AttribLocation {
+        match self.shader.get_attrib_location(name) {
+            Some(loc) => loc,
+            None => {
+                if self.is_required(name) {
+                    self.missing.push(fmt!("attribute %s", name));
+                }
+                AttribLocation { raw: -1 }
+            }
+        }
+    }
+
+    fn uniform(&mut self, name: &str) -> UniformLocation {
+        match self.shader.get_uniform_location(name) {
+            Some(loc) => loc,
+            None => {
+                if self.is_required(name) {
+                    self.missing.push(fmt!("uniform %s", name));
+                }
+                UniformLocation { raw: -1 }
+            }
+        }
+    }
+}
+
+/// The vertex attributes meshes feed from their buffers.
+pub struct Attribs {
+    coord: AttribLocation,
+    normal: AttribLocation,
+    color: AttribLocation,
+    texcoord: AttribLocation,
+}
+
+impl Attribs {
+    fn bind(binder: &mut Binder) -> Attribs {
+        Attribs {
+            coord: binder.attrib("v_coord"),
+            normal: binder.attrib("v_normal"),
+            color: binder.attrib("v_color"),
+            texcoord: binder.attrib("v_texcoord"),
+        }
+    }
+}
+
+/// The uniforms the camera and meshes set.
+pub struct Uniforms {
+    /// The model matrix, again under the name the `everything` shader uses.
+    model_orig: Mat4Uniform,
+    model: Mat4Uniform,
+    view: Mat4Uniform,
+    projection: Mat4Uniform,
+    model_inv_transp: Mat3Uniform,
+    highlight: FloatUniform,
+    textured: FloatUniform,
+    diffuse_map: SamplerUniform,
+    specular: Vec3Uniform,
+    shininess: FloatUniform,
+}
+
+impl Uniforms {
+    fn bind(binder: &mut Binder) -> Uniforms {
+        Uniforms {
+            model_orig: Mat4Uniform { loc: binder.uniform("m_orig") },
+            model: Mat4Uniform { loc: binder.uniform("m") },
+            view: Mat4Uniform { loc: binder.uniform("v") },
+            projection: Mat4Uniform { loc: binder.uniform("p") },
+            model_inv_transp: Mat3Uniform { loc: binder.uniform("m_inv_transp") },
+            highlight: FloatUniform { loc: binder.uniform("highlight") },
+            textured: FloatUniform { loc: binder.uniform("textured") },
+            diffuse_map: SamplerUniform { loc: binder.uniform("diffuse_map") },
+            specular: Vec3Uniform { loc: binder.uniform("specular") },
+            shininess: FloatUniform { loc: binder.uniform("shininess") },
+        }
+    }
+}
+
+/// Where a program's two stages come from, each with the inputs the shader
+/// must use on top of the ones every shader does.
+#[deriving(Clone)]
+pub enum ShaderSource {
+    /// `<name>.v.glsl` and `<name>.f.glsl` on disk, reloaded when edited.
+    Files(~str, &'static [&'static str]),
+    /// Built into the binary: a name, then the vertex and fragment sources.
+    Embedded(&'static str, &'static str, &'static str, &'static [&'static str]),
+}
+
+impl ShaderSource {
+    pub fn name(&self) -> ~str {
+        match *self {
+            Files(ref name, _) => name.clone(),
+            Embedded(name, _, _, _) => fmt!("%s (built in)", name)
+        }
+    }
+
+    pub fn required(&self) -> &'static [&'static str] {
+        match *self {
+            Files(_, required) | Embedded(_, _, _, required) => required
+        }
+    }
+}
+
+/// A shader with the locations meshes feed looked up once.
+pub struct Program {
+    source: ShaderSource,
+    shader: Shader,
+    attribs: Attribs,
+    uniforms: Uniforms,
+    mtime: Option<(i64, int)>,
+}
+
+impl Program {
+    /// Fails if either stage doesn't build, or the program lacks any of
+    /// the required inputs.
+    pub fn load(source: &ShaderSource) -> Result<Program, ShaderError> {
+        for source.required().iter().advance |&name| {
+            if !is_input(name) {
+                fail!(fmt!("%s: no input called %s to require", source.name(), name));
+            }
+        }
+        let (built, mtime) = match *source {
+            Files(ref name, _) => (Shader::from_files(fmt!("%s.v.glsl", *name), fmt!("%s.f.glsl", *name)),
+                                Program::source_mtime(*name)),
+            Embedded(name, vertex, fragment, _) =>
+                (Shader::from_sources(fmt!("%s.v.glsl", name), vertex.as_bytes().to_owned(),
+                                      fmt!("%s.f.glsl", name), fragment.as_bytes().to_owned()), None)
+        };
+        let shader = match built {
+            Ok(shader) => shader,
+            Err(error) => return Err(error)
+        };
+        
+        let (attribs, uniforms) = {
+            let mut binder = Binder { shader: &shader, required: source.required(), missing: ~[] };
+            let attribs = Attribs::bind(&mut binder);
+            let uniforms = Uniforms::bind(&mut binder);
+            if !binder.missing.is_empty() {
+                return Err(MissingInputs(source.name(), binder.missing));
             }
+            (attribs, uniforms)
+        };
+        
+        Ok(Program {
+            source: source.clone(),
+            shader: shader,
+            attribs: attribs,
+            uniforms: uniforms,
+            mtime: mtime,
+        })
+    }
+    
+    /// The later modification time of the two source files.
+    fn source_mtime(name: &str) -> Option<(i64, int)> {
+        let vertex = Path(fmt!("%s.v.glsl", name)).get_mtime();
+        let fragment = Path(fmt!("%s.f.glsl", name)).get_mtime();
+        match (vertex, fragment) {
+            (Some(v), Some(f)) => Some(if v > f { v } else { f }),
+            _ => None
+        }
+    }
+    
+    pub fn name(&self) -> ~str {
+        self.source.name()
+    }
+    
+    /// True once per edit of either source file. Built-in shaders never
+    /// change.
+    pub fn check_changed(&mut self) -> bool {
+        let mtime = match self.source {
+            Files(ref name, _) => Program::source_mtime(*name),
+            Embedded(*) => None
+        };
+        if mtime.is_some() && mtime != self.mtime {
+            self.mtime = mtime;
+            true
+        } else {
+            false
         }
     }
+    
+    pub fn bind(&self) {
+        self.shader.bind();
+    }
+}
+
+#[test]
+fn test_error_line() {
+    assert_eq!(error_line("0:12(5): error: `foo' undeclared"), Some(12));
+    assert_eq!(error_line("ERROR: 0:7: 'bar' : syntax error"), Some(7));
+    assert_eq!(error_line("0(31) : error C1008: undefined variable"), Some(31));
+    assert_eq!(error_line("Compile failed.\n0:3(1): error: syntax error"), Some(3));
+    assert_eq!(error_line("out of memory"), None);
+
+    let error = CompileError(~"shaders/lit.f.glsl", FragmentStage, Some(12), ~"0:12(5): error: oops\n");
+    assert_eq!(error.to_str(), ~"shaders/lit.f.glsl:12: fragment shader failed to compile: 0:12(5): error: oops");
 }
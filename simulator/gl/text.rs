@@ -0,0 +1,150 @@
+use std::uint;
+
+use math::Vec4;
+use gl::Mesh;
+
+use es = opengles::gl2;
+
+/// Glyphs are 5x7 pixels, one byte per row with the leftmost pixel in bit 4.
+static GLYPH_WIDTH: uint = 5;
+static GLYPH_HEIGHT: uint = 7;
+/// Room between characters and between lines, in glyph pixels.
+static ADVANCE: uint = 6;
+static LINE_HEIGHT: uint = 9;
+
+fn glyph(c: char) -> [u8, ..7] {
+    match c {
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '[' => [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E],
+        ']' => [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '\'' => [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        'a'..'z' => glyph((c as u8 - 32) as char),
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // '?'
+    }
+}
+
+/// Collects flat-colored rectangles and text in framebuffer pixels, with
+/// the origin at the top left, for `Camera::draw_overlay`.
+pub struct Overlay {
+    vertices: ~[Vec4<f32>],
+    colors: ~[Vec4<f32>],
+    elements: ~[es::GLuint],
+}
+
+impl Overlay {
+    pub fn new() -> Overlay {
+        Overlay { vertices: ~[], colors: ~[], elements: ~[] }
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Vec4<f32>) {
+        let base = self.vertices.len() as es::GLuint;
+        self.vertices.push(Vec4::new(x, y, 0.0, 1.0));
+        self.vertices.push(Vec4::new(x + width, y, 0.0, 1.0));
+        self.vertices.push(Vec4::new(x + width, y + height, 0.0, 1.0));
+        self.vertices.push(Vec4::new(x, y + height, 0.0, 1.0));
+        for 4.times {
+            self.colors.push(color.clone());
+        }
+        self.elements.push_all([base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    /// Draws `text` with its top left corner at (x, y), each glyph pixel
+    /// `scale` framebuffer pixels across. Lines are split on '\n'.
+    pub fn text(&mut self, x: f32, y: f32, scale: f32, color: Vec4<f32>, text: &str) {
+        let mut line_y = y;
+        for text.split_iter('\n').advance |line| {
+            for line.iter().enumerate().advance |(i, c)| {
+                let rows = glyph(c);
+                let glyph_x = x + (i * ADVANCE) as f32 * scale;
+                for uint::range(0, GLYPH_HEIGHT) |row| {
+                    // One rectangle per run of lit pixels keeps the vertex
+                    // count down
+                    let mut col = 0u;
+                    while col < GLYPH_WIDTH {
+                        if rows[row] & (0x10 >> col) == 0 {
+                            col += 1;
+                            loop;
+                        }
+                        let start = col;
+                        while col < GLYPH_WIDTH && rows[row] & (0x10 >> col) != 0 {
+                            col += 1;
+                        }
+                        self.rect(glyph_x + start as f32 * scale, line_y + row as f32 * scale,
+                                  (col - start) as f32 * scale, scale, color.clone());
+                    }
+                }
+            }
+            line_y += LINE_HEIGHT as f32 * scale;
+        }
+    }
+
+    pub fn text_size(text: &str, scale: f32) -> (f32, f32) {
+        let mut columns = 0u;
+        let mut lines = 0u;
+        for text.split_iter('\n').advance |line| {
+            columns = columns.max(&line.char_len());
+            lines += 1;
+        }
+        if columns == 0 {
+            return (0.0, 0.0);
+        }
+        (((columns - 1) * ADVANCE + GLYPH_WIDTH) as f32 * scale,
+         ((lines - 1) * LINE_HEIGHT + GLYPH_HEIGHT) as f32 * scale)
+    }
+
+    pub fn finish(self) -> ~[Mesh] {
+        let Overlay { vertices, colors, elements } = self;
+        if elements.len() == 0 {
+            return ~[];
+        }
+        ~[Mesh::new(vertices, colors, elements)]
+    }
+}
@@ -0,0 +1,229 @@
+use std::{str, uint, vec};
+use std::io;
+
+use es = opengles::gl2;
+
+#[macro_escape]
+#[path = "../check.rs"]
+mod check;
+
+/// RGBA pixels, eight bits a channel, with rows from the top down.
+pub struct Image {
+    width: uint,
+    height: uint,
+    pixels: ~[u8],
+}
+
+/// Reads a binary or plain PPM (`.ppm`) or a truecolor or grayscale TGA
+/// (`.tga`), picked by extension.
+pub fn load_image(path_str: &str) -> Result<Image, ~str> {
+    let bytes = match io::file_reader(&Path(path_str)) {
+        Ok(reader) => reader.read_whole_stream(),
+        Err(error) => return Err(fmt!("%s: %s", path_str, error))
+    };
+    let result = if path_str.ends_with(".ppm") || path_str.ends_with(".PPM") {
+        load_ppm(bytes)
+    } else if path_str.ends_with(".tga") || path_str.ends_with(".TGA") {
+        load_tga(bytes)
+    } else {
+        Err(~"only PPM and TGA images are supported")
+    };
+    match result {
+        Ok(image) => Ok(image),
+        Err(error) => Err(fmt!("%s: %s", path_str, error))
+    }
+}
+
+fn load_ppm(bytes: &[u8]) -> Result<Image, ~str> {
+    // The header is four whitespace-separated fields, with `#` comments
+    let mut fields = ~[];
+    let mut pos = 0u;
+    while fields.len() < 4 && pos < bytes.len() {
+        let c = bytes[pos] as char;
+        if c == '#' {
+            while pos < bytes.len() && bytes[pos] as char != '\n' {
+                pos += 1;
+            }
+        } else if c.is_whitespace() {
+            pos += 1;
+        } else {
+            let start = pos;
+            while pos < bytes.len() && !(bytes[pos] as char).is_whitespace() {
+                pos += 1;
+            }
+            fields.push(vec::from_slice(bytes.slice(start, pos)));
+        }
+    }
+    if fields.len() < 4 {
+        return Err(~"truncated PPM header");
+    }
+    let field = |i: uint| -> Option<uint> { uint::from_str_radix(str::from_bytes(fields[i]), 10) };
+    let (width, height, max) = match (field(1), field(2), field(3)) {
+        (Some(width), Some(height), Some(max)) if max > 0 && max < 256 => (width, height, max),
+        _ => return Err(~"bad PPM header, or more than 8 bits a channel")
+    };
+    let scale = |value: uint| (value * 255 / max) as u8;
+
+    let mut pixels = vec::with_capacity(width * height * 4);
+    let magic = str::from_bytes(fields[0]);
+    if magic == ~"P6" {
+        // Exactly one whitespace byte separates the header from the data
+        if pos + 1 + width * height * 3 > bytes.len() {
+            return Err(~"truncated PPM data");
+        }
+        let data = bytes.slice(pos + 1, bytes.len());
+        for uint::range(0, width * height) |i| {
+            pixels.push_all([scale(data[i * 3] as uint), scale(data[i * 3 + 1] as uint),
+                             scale(data[i * 3 + 2] as uint), 255]);
+        }
+    } else if magic == ~"P3" {
+        let text = str::from_bytes(bytes.slice(pos, bytes.len()));
+        let values: ~[uint] = text.word_iter().filter_map(|word| uint::from_str_radix(word, 10)).collect();
+        if values.len() < width * height * 3 {
+            return Err(~"truncated PPM data");
+        }
+        for uint::range(0, width * height) |i| {
+            pixels.push_all([scale(values[i * 3]), scale(values[i * 3 + 1]), scale(values[i * 3 + 2]), 255]);
+        }
+    } else {
+        return Err(~"not a P3 or P6 PPM");
+    }
+    Ok(Image { width: width, height: height, pixels: pixels })
+}
+
+fn load_tga(bytes: &[u8]) -> Result<Image, ~str> {
+    if bytes.len() < 18 {
+        return Err(~"truncated TGA header");
+    }
+    let id_length = bytes[0] as uint;
+    let image_type = bytes[2];
+    let width = bytes[12] as uint | (bytes[13] as uint << 8);
+    let height = bytes[14] as uint | (bytes[15] as uint << 8);
+    let depth = bytes[16] as uint / 8;
+    let top_down = bytes[17] & 0x20 != 0;
+    let (grayscale, compressed) = match image_type {
+        2 => (false, false),
+        3 => (true, false),
+        10 => (false, true),
+        11 => (true, true),
+        _ => return Err(~"only truecolor and grayscale TGAs are supported")
+    };
+    match (grayscale, depth) {
+        (true, 1) | (false, 3) | (false, 4) => {},
+        _ => return Err(fmt!("unsupported %u-bit TGA", depth * 8))
+    }
+
+    // Unpack to BGRA or gray pixels first, decoding runs if it's compressed
+    let mut pos = 18 + id_length + (bytes[5] as uint | (bytes[6] as uint << 8)) * (bytes[7] as uint / 8);
+    let count = width * height;
+    let mut raw: ~[u8] = vec::with_capacity(count * depth);
+    while raw.len() < count * depth {
+        let (repeat, literal) = if compressed {
+            if pos >= bytes.len() {
+                return Err(~"truncated TGA data");
+            }
+            let header = bytes[pos];
+            pos += 1;
+            let run = (header & 0x7F) as uint + 1;
+            if header & 0x80 != 0 { (run, 1) } else { (1, run) }
+        } else {
+            (1, count)
+        };
+        if pos + literal * depth > bytes.len() {
+            return Err(~"truncated TGA data");
+        }
+        for repeat.times {
+            raw.push_all(bytes.slice(pos, pos + literal * depth));
+        }
+        pos += literal * depth;
+    }
+
+    let mut pixels = vec::with_capacity(count * 4);
+    for uint::range(0, height) |row| {
+        // Rows are stored bottom up unless the descriptor says otherwise
+        let source_row = if top_down { row } else { height - 1 - row };
+        for uint::range(0, width) |col| {
+            let p = (source_row * width + col) * depth;
+            match depth {
+                1 => pixels.push_all([raw[p], raw[p], raw[p], 255]),
+                3 => pixels.push_all([raw[p + 2], raw[p + 1], raw[p], 255]),
+                _ => pixels.push_all([raw[p + 2], raw[p + 1], raw[p], raw[p + 3]])
+            }
+        }
+    }
+    Ok(Image { width: width, height: height, pixels: pixels })
+}
+
+/// An image waiting to be uploaded, or the GL texture it became.
+pub struct Texture {
+    image: Image,
+    id: es::GLuint,
+}
+
+impl Texture {
+    pub fn new(image: Image) -> Texture {
+        Texture { image: image, id: 0 }
+    }
+
+    /// Binds to texture unit 0, uploading first if need be.
+    pub fn bind(&mut self) {
+        if self.id == 0 {
+            self.upload();
+        }
+        check!(es::active_texture(es::TEXTURE0));
+        check!(es::bind_texture(es::TEXTURE_2D, self.id));
+    }
+
+    fn upload(&mut self) {
+        // Texture coordinates start at the bottom, so GL wants the rows
+        // bottom up
+        let row_len = self.image.width * 4;
+        let mut flipped = vec::with_capacity(self.image.pixels.len());
+        for uint::range(0, self.image.height) |n| {
+            let start = (self.image.height - 1 - n) * row_len;
+            flipped.push_all(self.image.pixels.slice(start, start + row_len));
+        }
+
+        self.id = check!(es::gen_textures(1)[0]);
+        check!(es::bind_texture(es::TEXTURE_2D, self.id));
+        check!(es::tex_image_2d(es::TEXTURE_2D, 0, es::RGBA as es::GLint, self.image.width as es::GLsizei,
+                                self.image.height as es::GLsizei, 0, es::RGBA, es::UNSIGNED_BYTE,
+                                Some(flipped.slice(0, flipped.len()))));
+        check!(es::generate_mipmap(es::TEXTURE_2D));
+        check!(es::tex_parameter_i(es::TEXTURE_2D, es::TEXTURE_MIN_FILTER, es::LINEAR_MIPMAP_LINEAR as es::GLint));
+        check!(es::tex_parameter_i(es::TEXTURE_2D, es::TEXTURE_MAG_FILTER, es::LINEAR as es::GLint));
+        check!(es::tex_parameter_i(es::TEXTURE_2D, es::TEXTURE_WRAP_S, es::REPEAT as es::GLint));
+        check!(es::tex_parameter_i(es::TEXTURE_2D, es::TEXTURE_WRAP_T, es::REPEAT as es::GLint));
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&self) {
+        if self.id != 0 {
+            es::delete_textures([self.id]);
+        }
+    }
+}
+
+#[test]
+fn test_ppm() {
+    let image = match load_ppm(bytes!("P3\n# two pixels\n2 1\n15\n15 0 0  0 15 15\n")) {
+        Ok(image) => image,
+        Err(error) => fail!(error)
+    };
+    assert_eq!((image.width, image.height), (2, 1));
+    assert_eq!(image.pixels, ~[255, 0, 0, 255, 0, 255, 255, 255]);
+}
+
+#[test]
+fn test_tga_rle() {
+    // A 2x2 bottom-up 24-bit image: one run of three blue pixels, then a
+    // literal red one
+    let mut bytes = ~[0u8, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 2, 0, 24, 0];
+    bytes.push_all([0x82, 255, 0, 0, 0x00, 0, 0, 255]);
+    let image = match load_tga(bytes) {
+        Ok(image) => image,
+        Err(error) => fail!(error)
+    };
+    assert_eq!(image.pixels, ~[0, 0, 255, 255, 255, 0, 0, 255, 0, 0, 255, 255, 0, 0, 255, 255]);
+}
@@ -0,0 +1,307 @@
+use std::{f32, int, uint, util, vec};
+
+use math::{Vec3, Vec4};
+
+/// One corner of a face, as 0-based indices into the model's lists.
+#[deriving(Clone, Eq)]
+pub struct Corner {
+    position: uint,
+    texcoord: Option<uint>,
+    normal: Option<uint>,
+}
+
+/// The faces under one `o` or `g` name with the same material, already
+/// cut into triangles of three corners each.
+pub struct Group {
+    name: ~str,
+    material: Option<~str>,
+    triangles: ~[Corner],
+}
+
+/// Everything read from an OBJ file. Groups share the vertex data.
+pub struct Model {
+    positions: ~[Vec4<f32>],
+    texcoords: ~[Vec3<f32>],
+    normals: ~[Vec3<f32>],
+    /// MTL files named by `mtllib`, as written.
+    libraries: ~[~str],
+    groups: ~[Group],
+}
+
+/// Reads an OBJ file, naming it `source` in errors. Faces can be any
+/// simple polygon, with `v`, `v/vt`, `v//vn` or `v/vt/vn` corners and
+/// negative indices counting back from the latest vertex. Statements the
+/// simulator has no use for are skipped.
+pub fn parse(reader: @Reader, source: &str) -> Result<Model, ~str> {
+    let mut model = Model { positions: ~[], texcoords: ~[], normals: ~[], libraries: ~[],
+                            groups: ~[Group { name: ~"default", material: None, triangles: ~[] }] };
+    let mut line_num = 0u;
+    let mut error = None;
+    for reader.each_line |line| {
+        line_num += 1;
+        match parse_line(&mut model, line) {
+            Ok(()) => {},
+            Err(message) => {
+                error = Some(fmt!("%s:%u: %s", source, line_num, message));
+                break;
+            }
+        }
+    }
+    match error {
+        Some(error) => return Err(error),
+        None => {}
+    }
+
+    // Groups are started by name, so some never get any faces
+    let groups = util::replace(&mut model.groups, ~[]);
+    for groups.consume_iter().advance |group| {
+        if group.triangles.len() > 0 {
+            model.groups.push(group);
+        }
+    }
+    Ok(model)
+}
+
+fn parse_line(model: &mut Model, line: &str) -> Result<(), ~str> {
+    let line = match line.find('#') {
+        Some(i) => line.slice_to(i),
+        None => line
+    };
+    let words: ~[&str] = line.word_iter().collect();
+    if words.len() == 0 {
+        return Ok(());
+    }
+    let args = words.tail();
+
+    match words[0] {
+        "v" => match parse_floats(args, 3, 4) {
+            Ok(v) => {
+                let w = if v.len() == 4 { v[3] } else { 1.0 };
+                model.positions.push(Vec4::new(v[0], v[1], v[2], w));
+            },
+            Err(error) => return Err(error)
+        },
+        "vt" => match parse_floats(args, 1, 3) {
+            Ok(v) => {
+                let get = |i: uint| if i < v.len() { v[i] } else { 0.0 };
+                model.texcoords.push(Vec3::new(get(0), get(1), get(2)));
+            },
+            Err(error) => return Err(error)
+        },
+        "vn" => match parse_floats(args, 3, 3) {
+            Ok(v) => model.normals.push(Vec3::new(v[0], v[1], v[2])),
+            Err(error) => return Err(error)
+        },
+        "f" => {
+            if args.len() < 3 {
+                return Err(~"faces need at least three corners");
+            }
+            let mut corners = ~[];
+            for args.iter().advance |&word| {
+                match parse_corner(model, word) {
+                    Ok(corner) => corners.push(corner),
+                    Err(error) => return Err(error)
+                }
+            }
+            let positions = do corners.map |corner| { Vec3::from4(model.positions[corner.position].clone()) };
+            let triangles = triangulate(positions);
+            let last = model.groups.len() - 1;
+            let group = &mut model.groups[last];
+            for triangles.iter().advance |&(a, b, c)| {
+                group.triangles.push_all([corners[a].clone(), corners[b].clone(), corners[c].clone()]);
+            }
+        },
+        "o" | "g" => {
+            let name = if args.len() > 0 { args.connect(" ") } else { ~"default" };
+            let last = model.groups.len() - 1;
+            if model.groups[last].triangles.len() == 0 {
+                model.groups[last].name = name;
+            } else {
+                let material = model.groups[last].material.clone();
+                model.groups.push(Group { name: name, material: material, triangles: ~[] });
+            }
+        },
+        "usemtl" => {
+            if args.len() == 0 {
+                return Err(~"usemtl needs a material name");
+            }
+            let material = Some(args.connect(" "));
+            let last = model.groups.len() - 1;
+            if model.groups[last].triangles.len() == 0 {
+                model.groups[last].material = material;
+            } else {
+                let name = model.groups[last].name.clone();
+                model.groups.push(Group { name: name, material: material, triangles: ~[] });
+            }
+        },
+        "mtllib" => {
+            for args.iter().advance |&library| {
+                model.libraries.push(library.to_owned());
+            }
+        },
+        _ => {}
+    }
+    Ok(())
+}
+
+fn parse_floats(words: &[&str], min: uint, max: uint) -> Result<~[f32], ~str> {
+    if words.len() < min || words.len() > max {
+        return Err(if min == max { fmt!("expected %u numbers", min) }
+                   else { fmt!("expected %u to %u numbers", min, max) });
+    }
+    let mut values = ~[];
+    for words.iter().advance |&word| {
+        match f32::from_str(word) {
+            Some(value) => values.push(value),
+            None => return Err(fmt!("\"%s\" isn't a number", word))
+        }
+    }
+    Ok(values)
+}
+
+fn parse_corner(model: &Model, word: &str) -> Result<Corner, ~str> {
+    let parts: ~[&str] = word.split_iter('/').collect();
+    if parts.len() > 3 {
+        return Err(fmt!("\"%s\" isn't a face corner", word));
+    }
+    let position = match resolve_index(parts[0], model.positions.len()) {
+        Ok(index) => index,
+        Err(error) => return Err(fmt!("vertex %s", error))
+    };
+    let texcoord = if parts.len() > 1 && parts[1].len() > 0 {
+        match resolve_index(parts[1], model.texcoords.len()) {
+            Ok(index) => Some(index),
+            Err(error) => return Err(fmt!("texture coordinate %s", error))
+        }
+    } else {
+        None
+    };
+    let normal = if parts.len() > 2 && parts[2].len() > 0 {
+        match resolve_index(parts[2], model.normals.len()) {
+            Ok(index) => Some(index),
+            Err(error) => return Err(fmt!("normal %s", error))
+        }
+    } else {
+        None
+    };
+    Ok(Corner { position: position, texcoord: texcoord, normal: normal })
+}
+
+/// OBJ indices count from 1, or back from the end of the list so far if
+/// they're negative.
+fn resolve_index(word: &str, len: uint) -> Result<uint, ~str> {
+    match int::from_str(word) {
+        Some(index) if index > 0 && (index as uint) <= len => Ok(index as uint - 1),
+        Some(index) if index < 0 && ((-index) as uint) <= len => Ok(len - (-index) as uint),
+        Some(_) => Err(fmt!("index %s is out of range, with %u so far", word, len)),
+        None => Err(fmt!("index \"%s\" isn't a number", word))
+    }
+}
+
+/// Cuts a simple polygon, convex or not, into triangles by clipping ears.
+/// Returns indices into `polygon`, keeping its winding.
+pub fn triangulate(polygon: &[Vec3<f32>]) -> ~[(uint, uint, uint)] {
+    let n = polygon.len();
+    if n < 3 {
+        return ~[];
+    }
+    if n == 3 {
+        return ~[(0, 1, 2)];
+    }
+
+    // Newell's method gives the polygon's facing even when it isn't planar
+    let mut normal = Vec3::new(0.0f32, 0.0, 0.0);
+    for uint::range(0, n) |i| {
+        let (a, b) = (&polygon[i], &polygon[(i + 1) % n]);
+        normal = normal + Vec3::new((a.y - b.y) * (a.z + b.z), (a.z - b.z) * (a.x + b.x),
+                                    (a.x - b.x) * (a.y + b.y));
+    }
+
+    let mut remaining = vec::from_fn(n, |i| i);
+    let mut triangles = ~[];
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let mut clipped = false;
+        for uint::range(0, count) |i| {
+            let (prev, cur, next) = (remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]);
+            if is_ear(polygon, remaining, prev, cur, next, &normal) {
+                triangles.push((prev, cur, next));
+                remaining.remove(i);
+                clipped = true;
+                break;
+            }
+        }
+        if !clipped {
+            // Degenerate or self-intersecting; a fan is as good as anything
+            for uint::range(1, remaining.len() - 1) |i| {
+                triangles.push((remaining[0], remaining[i], remaining[i + 1]));
+            }
+            return triangles;
+        }
+    }
+    triangles.push((remaining[0], remaining[1], remaining[2]));
+    triangles
+}
+
+fn is_ear(polygon: &[Vec3<f32>], remaining: &[uint], prev: uint, cur: uint, next: uint,
+          normal: &Vec3<f32>) -> bool {
+    let (a, b, c) = (&polygon[prev], &polygon[cur], &polygon[next]);
+    // Reflex corners turn against the polygon's facing
+    if (*b - *a).cross(&(*c - *b)).dot(normal) <= 0.0 {
+        return false;
+    }
+    for remaining.iter().advance |&other| {
+        if other == prev || other == cur || other == next {
+            loop;
+        }
+        let p = &polygon[other];
+        let inside = (*b - *a).cross(&(*p - *a)).dot(normal) >= 0.0 &&
+                     (*c - *b).cross(&(*p - *b)).dot(normal) >= 0.0 &&
+                     (*a - *c).cross(&(*p - *c)).dot(normal) >= 0.0;
+        if inside {
+            return false;
+        }
+    }
+    true
+}
+
+#[test]
+fn test_quad_and_negative_indices() {
+    let text = "v 0 0 0\nv 1 0 0\nv  1 1 0\nv 0\t1 0\nvn 0 0 1\nf -4//1 -3//1 -2//1 -1//1\n";
+    let model = match do std::io::with_str_reader(text) |reader| { parse(reader, "quad.obj") } {
+        Ok(model) => model,
+        Err(error) => fail!(error)
+    };
+    assert_eq!(model.positions.len(), 4);
+    assert_eq!(model.groups.len(), 1);
+    assert_eq!(model.groups[0].triangles.len(), 6);
+    for model.groups[0].triangles.iter().advance |corner| {
+        assert_eq!(corner.normal, Some(0));
+    }
+}
+
+#[test]
+fn test_concave_polygon() {
+    // An L shape, whose corner at (1, 1) a fan from the first vertex would
+    // cut across
+    let polygon = [Vec3::new(0.0f32, 0.0, 0.0), Vec3::new(2.0f32, 0.0, 0.0), Vec3::new(2.0f32, 1.0, 0.0),
+                   Vec3::new(1.0f32, 1.0, 0.0), Vec3::new(1.0f32, 2.0, 0.0), Vec3::new(0.0f32, 2.0, 0.0)];
+    let triangles = triangulate(polygon);
+    assert_eq!(triangles.len(), 4);
+    let mut area = 0.0f32;
+    for triangles.iter().advance |&(a, b, c)| {
+        let cross = (polygon[b] - polygon[a]).cross(&(polygon[c] - polygon[a]));
+        assert!(cross.z > 0.0);
+        area += cross.z / 2.0;
+    }
+    assert!((area - 3.0).abs() < 0.001);
+}
+
+#[test]
+fn test_error_line_numbers() {
+    let text = "# a triangle\nv 0 0 0\nv 1 0 0\nf 1 2 3\n";
+    match do std::io::with_str_reader(text) |reader| { parse(reader, "bad.obj") } {
+        Ok(_) => fail!("parsed a face with a missing vertex"),
+        Err(error) => assert_eq!(error, ~"bad.obj:4: vertex index 3 is out of range, with 2 so far")
+    }
+}
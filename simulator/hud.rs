@@ -0,0 +1,142 @@
+use extra::time;
+
+use math::{Vec3, Vec4};
+use gl::camera::Camera;
+use gl::text::Overlay;
+use layout::Rod;
+use server::Clients;
+
+/// How often the FPS readout updates, in seconds.
+static FPS_INTERVAL: float = 0.5;
+
+/// Debugging overlay with each rod's label and color, the frame rate and
+/// the connected clients, plus an inspector for the selected rod.
+pub struct Hud {
+    visible: bool,
+    frames: uint,
+    fps_start: float,
+    fps: float,
+}
+
+impl Hud {
+    pub fn new() -> Hud {
+        Hud { visible: false, frames: 0, fps_start: time::precise_time_s(), fps: 0.0 }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Call once a frame, after the scene is rendered, whether or not the
+    /// HUD is showing, so the frame rate stays current.
+    pub fn draw(&mut self, camera: &mut Camera, rods: &[Rod], clients: Option<&Clients>,
+                selected: Option<uint>) {
+        let now = time::precise_time_s();
+        self.frames += 1;
+        if now - self.fps_start >= FPS_INTERVAL {
+            self.fps = (self.frames as float) / (now - self.fps_start);
+            self.frames = 0;
+            self.fps_start = now;
+        }
+        if !self.visible && selected.is_none() {
+            return;
+        }
+
+        let scale = 2.0 * camera.pixel_ratio();
+        let background = Vec4::new(0.0f32, 0.0, 0.0, 0.5);
+        let white = Vec4::new(1.0f32, 1.0, 1.0, 1.0);
+        let mut overlay = Overlay::new();
+
+        match selected {
+            Some(index) if index < rods.len() => {
+                let text = inspect(&rods[index], camera, now);
+                let (width, height) = Overlay::text_size(text, scale);
+                let left = (camera.width as f32) - width - 8.0 * scale;
+                overlay.rect(left, 0.0, width + 8.0 * scale, height + 8.0 * scale, background.clone());
+                overlay.text(left + 4.0 * scale, 4.0 * scale, scale, white.clone(), text);
+            },
+            _ => {}
+        }
+
+        if !self.visible {
+            let mut meshes = overlay.finish();
+            camera.draw_overlay(meshes);
+            return;
+        }
+
+        for rods.iter().advance |rod| {
+            let rgb = match camera.rod(rod.index) {
+                Some(mesh) => Some(mesh.rgb),
+                None => None
+            };
+            let above = rod.top() + Vec3::new(0.0, 0.02, 0.0);
+            match (rgb, camera.project(above)) {
+                (Some((r, g, b)), Some((x, y))) => {
+                    let label = fmt!("%s\n%u %u %u", rod.label(), r as uint, g as uint, b as uint);
+                    let (width, height) = Overlay::text_size(label, scale);
+                    let (left, top) = (x - width / 2.0, y - height);
+                    overlay.rect(left - scale, top - scale, width + 2.0 * scale, height + 2.0 * scale,
+                                 background.clone());
+                    overlay.text(left, top, scale, white.clone(), label);
+                },
+                _ => {}
+            }
+        }
+
+        // Name each view when there's more than one
+        let panes = camera.panes();
+        if panes.len() > 1 {
+            for panes.iter().advance |&(view, (left, top, _, height))| {
+                let name = view.name();
+                let (width, text_height) = Overlay::text_size(name, scale);
+                let bottom = (top + height) as f32;
+                overlay.rect(left as f32, bottom - text_height - 8.0 * scale, width + 8.0 * scale,
+                             text_height + 8.0 * scale, background.clone());
+                overlay.text(left as f32 + 4.0 * scale, bottom - text_height - 4.0 * scale, scale,
+                             white.clone(), name);
+            }
+        }
+
+        let mut status = fmt!("FPS %.1f", self.fps);
+        match clients {
+            Some(clients) => {
+                status.push_str(fmt!("\nCLIENTS %u", clients.clients.len()));
+                for clients.clients.iter().advance |(_, client)| {
+                    status.push_str(fmt!("\n%s  %.1f MSG/S  SEEN %.1fS AGO",
+                                         client.addr, client.rate, now - client.last_seen));
+                }
+            },
+            None => status.push_str("\nSERVER OFF")
+        }
+        let (width, height) = Overlay::text_size(status, scale);
+        overlay.rect(0.0, 0.0, width + 8.0 * scale, height + 8.0 * scale, background.clone());
+        overlay.text(4.0 * scale, 4.0 * scale, scale, white.clone(), status);
+
+        let mut meshes = overlay.finish();
+        camera.draw_overlay(meshes);
+    }
+}
+
+fn inspect(rod: &Rod, camera: &Camera, now: float) -> ~str {
+    let mut text = fmt!("ROD %s\nPOSITION %.2f, %.2f\nHEIGHT %.2f", rod.label(), rod.x as float,
+                        rod.y as float, rod.height as float);
+    text.push_str(fmt!("\nFALLOFF %.2f  END GLOW %.2f", rod.diffusion.falloff as float,
+                       rod.diffusion.end_glow as float));
+    if rod.tags.len() > 0 {
+        text.push_str(fmt!("\nTAGS %s", rod.tags.connect(", ")));
+    }
+    match camera.rod(rod.index) {
+        Some(mesh) => {
+            let (r, g, b) = mesh.rgb;
+            text.push_str(fmt!("\nCOLOR %u %u %u", r as uint, g as uint, b as uint));
+            if mesh.history.len() > 0 {
+                text.push_str("\nRECENT");
+                for mesh.history.rev_iter().advance |&(time, (r, g, b))| {
+                    text.push_str(fmt!("\n  %.1fS AGO  %u %u %u", now - time, r as uint, g as uint, b as uint));
+                }
+            }
+        },
+        None => {}
+    }
+    text
+}
@@ -0,0 +1,176 @@
+use extra::json;
+
+use math::Vec3;
+use config;
+use assets;
+
+/// Layout files are in tenths of a scene unit.
+pub static SCENE_SCALE: f32 = 0.1;
+
+/// Diffusion for rods that don't give their own, roughly matching the
+/// acrylic in the piece.
+static DEFAULT_FALLOFF: f32 = 8.0;
+static DEFAULT_END_GLOW: f32 = 0.5;
+/// How far down from the top the end glow reaches, about a rod's radius.
+static END_GLOW_LENGTH: f32 = 0.2;
+
+/// How light from the LED at the bottom of a rod spreads along it.
+pub struct Diffusion {
+    /// Height over which light falls to 1/e of the LED's, or 0 for none.
+    falloff: f32,
+    /// Extra light, relative to the LED's, where it escapes at the top end.
+    end_glow: f32,
+}
+
+impl Diffusion {
+    /// Relative light `along` layout units up a rod `height` tall.
+    pub fn brightness(&self, along: f32, height: f32) -> f32 {
+        let falloff = if self.falloff > 0.0 { (-along / self.falloff).exp() } else { 1.0 };
+        falloff + self.end_glow * (-(height - along) / END_GLOW_LENGTH).exp()
+    }
+}
+
+/// One rod from a layout file, in layout units.
+pub struct Rod {
+    index: uint,
+    id: Option<~str>,
+    x: f32,
+    y: f32,
+    height: f32,
+    tags: ~[~str],
+    diffusion: Diffusion,
+}
+
+impl Rod {
+    pub fn base(&self) -> Vec3<f32> {
+        Vec3::new(self.x * SCENE_SCALE, 0.0, self.y * SCENE_SCALE)
+    }
+
+    pub fn top(&self) -> Vec3<f32> {
+        Vec3::new(self.x * SCENE_SCALE, self.height * SCENE_SCALE, self.y * SCENE_SCALE)
+    }
+
+    /// The ID if the layout gives one, otherwise the index.
+    pub fn label(&self) -> ~str {
+        match self.id {
+            Some(ref id) => fmt!("%u %s", self.index, *id),
+            None => fmt!("%u", self.index)
+        }
+    }
+}
+
+/// Layouts are a JSON list of rods, each placed either with `x` and `y` or
+/// with `angle` and `radius`, plus a `height`, an optional string `id`, an
+/// optional list of string `tags` and an optional `diffusion` object with
+/// `falloff` and `end_glow`.
+pub fn load_layout(path_str: &str) -> Result<~[Rod], ~str> {
+    match config::read_json_file(path_str) {
+        Ok(json) => parse_layout(json, path_str),
+        Err(error) => Err(error)
+    }
+}
+
+/// The layout built into the binary, for when none is given.
+pub fn default_layout() -> Result<~[Rod], ~str> {
+    let source = "default-rods.json (built in)";
+    match config::read_json_str(assets::DEFAULT_RODS, source) {
+        Ok(json) => parse_layout(json, source),
+        Err(error) => Err(error)
+    }
+}
+
+fn parse_layout(json: json::Json, path_str: &str) -> Result<~[Rod], ~str> {
+    let list = match json {
+        json::List(list) => list,
+        _ => return Err(fmt!("%s: expected a list of rods", path_str))
+    };
+
+    let mut rods = ~[];
+    for list.iter().enumerate().advance |(index, json)| {
+        match parse_rod(index, json) {
+            Ok(rod) => rods.push(rod),
+            Err(error) => return Err(fmt!("%s: rod %u: %s", path_str, index, error))
+        }
+    }
+    Ok(rods)
+}
+
+fn parse_rod(index: uint, json: &json::Json) -> Result<Rod, ~str> {
+    let obj = match *json {
+        json::Object(ref obj) => &**obj,
+        _ => return Err(~"expected an object")
+    };
+
+    let (x, y) = if obj.contains_key_equiv(&("x")) || obj.contains_key_equiv(&("y")) {
+        match (config::find_number(obj, "x"), config::find_number(obj, "y")) {
+            (Ok(x), Ok(y)) => (x, y),
+            (Err(error), _) | (_, Err(error)) => return Err(error)
+        }
+    } else if obj.contains_key_equiv(&("angle")) || obj.contains_key_equiv(&("radius")) {
+        match (config::find_number(obj, "angle"), config::find_number(obj, "radius")) {
+            (Ok(angle), Ok(radius)) => (radius * angle.cos(), radius * angle.sin()),
+            (Err(error), _) | (_, Err(error)) => return Err(error)
+        }
+    } else {
+        return Err(~"needs either \"x\" and \"y\" or \"angle\" and \"radius\"");
+    };
+    let height = match config::find_number(obj, "height") {
+        Ok(height) => height,
+        Err(error) => return Err(error)
+    };
+    let id = match obj.find_equiv(&("id")) {
+        Some(&json::String(ref id)) => Some(id.clone()),
+        Some(_) => return Err(~"\"id\" should be a string"),
+        None => None
+    };
+    let mut tags = ~[];
+    match obj.find_equiv(&("tags")) {
+        Some(&json::List(ref list)) => {
+            for list.iter().advance |tag| {
+                match *tag {
+                    json::String(ref tag) => tags.push(tag.clone()),
+                    _ => return Err(~"\"tags\" should be a list of strings")
+                }
+            }
+        },
+        Some(_) => return Err(~"\"tags\" should be a list of strings"),
+        None => {}
+    }
+    let diffusion = match obj.find_equiv(&("diffusion")) {
+        Some(&json::Object(ref diffusion)) => match parse_diffusion(&**diffusion) {
+            Ok(diffusion) => diffusion,
+            Err(error) => return Err(fmt!("diffusion: %s", error))
+        },
+        Some(_) => return Err(~"\"diffusion\" should be an object"),
+        None => Diffusion { falloff: DEFAULT_FALLOFF, end_glow: DEFAULT_END_GLOW }
+    };
+
+    Ok(Rod { index: index, id: id, x: x as f32, y: y as f32, height: height as f32, tags: tags,
+             diffusion: diffusion })
+}
+
+fn parse_diffusion(obj: &json::Object) -> Result<Diffusion, ~str> {
+    let mut diffusion = Diffusion { falloff: DEFAULT_FALLOFF, end_glow: DEFAULT_END_GLOW };
+    if obj.contains_key_equiv(&("falloff")) {
+        match config::find_number(obj, "falloff") {
+            Ok(falloff) if falloff >= 0.0 => diffusion.falloff = falloff as f32,
+            Ok(_) => return Err(~"\"falloff\" can't be negative"),
+            Err(error) => return Err(error)
+        }
+    }
+    if obj.contains_key_equiv(&("end_glow")) {
+        match config::find_number(obj, "end_glow") {
+            Ok(glow) if glow >= 0.0 => diffusion.end_glow = glow as f32,
+            Ok(_) => return Err(~"\"end_glow\" can't be negative"),
+            Err(error) => return Err(error)
+        }
+    }
+    Ok(diffusion)
+}
+
+#[test]
+fn test_default_layout() {
+    let rods = default_layout().unwrap();
+    assert!(rods.len() > 0);
+    assert_eq!(rods[0].index, 0);
+}
@@ -0,0 +1,156 @@
+use extra::json;
+
+use math::Vec3;
+use config;
+
+/// Models how a byte sent to a rod turns into the light it gives off, and
+/// how to show that light on a monitor.
+///
+/// The LED drivers are linear in PWM duty cycle, so a byte of 128 gives half
+/// the light of 255. Monitors aren't: they expect gamma-encoded values, so
+/// the light has to be encoded with `display_gamma` to look right on screen.
+#[deriving(Clone)]
+pub struct LedResponse {
+    /// Exponent from byte to duty cycle; 1.0 for plain PWM, or whatever
+    /// correction the firmware applies.
+    gamma: f32,
+    /// Relative brightness of each channel at full duty.
+    max_brightness: Vec3<f32>,
+    /// Color temperature of full white, in kelvin.
+    white_temperature: f32,
+    /// Duty cycle below which an LED doesn't visibly light at all.
+    min_level: f32,
+    display_gamma: f32,
+}
+
+/// The white point the simulator's screen is assumed to have.
+static NEUTRAL_TEMPERATURE: f32 = 6500.0;
+
+impl LedResponse {
+    pub fn new() -> LedResponse {
+        LedResponse {
+            gamma: 1.0,
+            max_brightness: Vec3::new(1.0f32, 1.0, 1.0),
+            white_temperature: NEUTRAL_TEMPERATURE,
+            min_level: 0.0,
+            display_gamma: 2.2,
+        }
+    }
+
+    /// Reads a JSON object with any of `gamma`, `max_brightness` (a list of
+    /// three numbers), `white_temperature`, `min_level` and `display_gamma`.
+    /// Anything missing keeps its default.
+    pub fn load(path_str: &str) -> Result<LedResponse, ~str> {
+        let obj = match config::read_json_file(path_str) {
+            Ok(json::Object(obj)) => obj,
+            Ok(_) => return Err(fmt!("%s: expected an object", path_str)),
+            Err(error) => return Err(error)
+        };
+
+        let mut response = LedResponse::new();
+        match optional_number(&*obj, "gamma", response.gamma) {
+            Ok(gamma) if gamma > 0.0 => response.gamma = gamma,
+            Ok(_) => return Err(fmt!("%s: \"gamma\" should be positive", path_str)),
+            Err(error) => return Err(fmt!("%s: %s", path_str, error))
+        }
+        match optional_number(&*obj, "white_temperature", response.white_temperature) {
+            Ok(kelvin) => response.white_temperature = kelvin,
+            Err(error) => return Err(fmt!("%s: %s", path_str, error))
+        }
+        match optional_number(&*obj, "min_level", response.min_level) {
+            Ok(level) => response.min_level = level,
+            Err(error) => return Err(fmt!("%s: %s", path_str, error))
+        }
+        match optional_number(&*obj, "display_gamma", response.display_gamma) {
+            Ok(gamma) if gamma > 0.0 => response.display_gamma = gamma,
+            Ok(_) => return Err(fmt!("%s: \"display_gamma\" should be positive", path_str)),
+            Err(error) => return Err(fmt!("%s: %s", path_str, error))
+        }
+        if obj.contains_key_equiv(&("max_brightness")) {
+            match config::find_vec3(&*obj, "max_brightness") {
+                Ok(v) => response.max_brightness = v,
+                Err(error) => return Err(fmt!("%s: %s", path_str, error))
+            }
+        }
+        Ok(response)
+    }
+
+    /// The color to draw for a byte triple, with each channel from 0 to 1.
+    pub fn to_display(&self, r: u8, g: u8, b: u8) -> (f32, f32, f32) {
+        let white = white_balance(self.white_temperature);
+        (self.channel(r, self.max_brightness.x * white.x),
+         self.channel(g, self.max_brightness.y * white.y),
+         self.channel(b, self.max_brightness.z * white.z))
+    }
+
+    /// Encodes relative light for the screen. Scaling a displayed color by
+    /// `encode(x)` is the same as scaling its light by `x`.
+    pub fn encode(&self, light: f32) -> f32 {
+        light.max(&0.0).pow(&(1.0 / self.display_gamma))
+    }
+
+    fn channel(&self, byte: u8, scale: f32) -> f32 {
+        let duty = ((byte as f32) / 255.0).pow(&self.gamma);
+        if duty <= 0.0 || duty < self.min_level {
+            return 0.0;
+        }
+        self.encode((duty * scale).min(&1.0))
+    }
+}
+
+fn optional_number(obj: &json::Object, key: &str, default: f32) -> Result<f32, ~str> {
+    if !obj.contains_key_equiv(&key) {
+        return Ok(default);
+    }
+    match config::find_number(obj, key) {
+        Ok(num) if num >= 0.0 => Ok(num as f32),
+        Ok(_) => Err(fmt!("\"%s\" can't be negative", key)),
+        Err(error) => Err(error)
+    }
+}
+
+/// Linear RGB multipliers that tint white toward `kelvin`, relative to the
+/// screen's own white. Based on Tanner Helland's fit to blackbody colors.
+fn white_balance(kelvin: f32) -> Vec3<f32> {
+    let target = blackbody(kelvin);
+    let neutral = blackbody(NEUTRAL_TEMPERATURE);
+    Vec3::new(target.x / neutral.x, target.y / neutral.y, target.z / neutral.z)
+}
+
+fn blackbody(kelvin: f32) -> Vec3<f32> {
+    let t = kelvin.max(&1000.0).min(&40000.0) / 100.0;
+    let r = if t <= 66.0 { 255.0 } else { 329.698727446 * (t - 60.0).pow(&-0.1332047592) };
+    let g = if t <= 66.0 {
+        99.4708025861 * t.ln() - 161.1195681661
+    } else {
+        288.1221695283 * (t - 60.0).pow(&-0.0755148492)
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.5177312231 * (t - 10.0).ln() - 305.0447927307
+    };
+    // The fit is in gamma-encoded bytes; multipliers need linear light
+    let linear = |c: f32| (c.max(&0.0).min(&255.0) / 255.0).pow(&2.2f32).max(&0.0001);
+    Vec3::new(linear(r), linear(g), linear(b))
+}
+
+#[test]
+fn test_neutral_response() {
+    let response = LedResponse::new();
+    assert_eq!(response.to_display(0, 0, 0), (0.0, 0.0, 0.0));
+    let (r, g, b) = response.to_display(255, 255, 255);
+    assert!((r - 1.0).abs() < 0.001 && (g - 1.0).abs() < 0.001 && (b - 1.0).abs() < 0.001);
+}
+
+#[test]
+fn test_min_level() {
+    let mut response = LedResponse::new();
+    response.min_level = 0.1;
+    let (r, _, _) = response.to_display(20, 0, 0);
+    assert_eq!(r, 0.0);
+    let (r, _, _) = response.to_display(30, 0, 0);
+    assert!(r > 0.0);
+}
@@ -0,0 +1,283 @@
+use std::f32;
+
+use math::Vec3;
+
+#[cfg(test)]
+use math::close;
+
+/// A half-line from `origin` along `dir`. Distances along it are in units of
+/// `dir`'s length, so they're true distances when `dir` is normalized.
+#[deriving(Clone)]
+pub struct Ray {
+    origin: Vec3<f32>,
+    dir: Vec3<f32>,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3<f32>, dir: Vec3<f32>) -> Ray {
+        Ray { origin: origin, dir: dir }
+    }
+
+    /// The point `t` along the ray.
+    pub fn at(&self, t: f32) -> Vec3<f32> {
+        self.origin + self.dir * t
+    }
+}
+
+/// An axis-aligned box from its lowest corner to its highest.
+#[deriving(Clone)]
+pub struct Aabb {
+    low: Vec3<f32>,
+    high: Vec3<f32>,
+}
+
+impl Aabb {
+    pub fn new(low: Vec3<f32>, high: Vec3<f32>) -> Aabb {
+        Aabb { low: low, high: high }
+    }
+
+    /// The smallest box around all of `points`, or `None` if there are none.
+    pub fn from_points(points: &[Vec3<f32>]) -> Option<Aabb> {
+        if points.is_empty() {
+            return None;
+        }
+        let mut bounds = Aabb::new(points[0].clone(), points[0].clone());
+        for points.iter().advance |p| {
+            bounds.extend(p);
+        }
+        Some(bounds)
+    }
+
+    /// Grows the box to take in `p`.
+    pub fn extend(&mut self, p: &Vec3<f32>) {
+        self.low = Vec3::new(self.low.x.min(&p.x), self.low.y.min(&p.y), self.low.z.min(&p.z));
+        self.high = Vec3::new(self.high.x.max(&p.x), self.high.y.max(&p.y), self.high.z.max(&p.z));
+    }
+
+    /// The smallest box around both boxes.
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let mut bounds = self.clone();
+        bounds.extend(&other.low);
+        bounds.extend(&other.high);
+        bounds
+    }
+
+    pub fn center(&self) -> Vec3<f32> {
+        (self.low + self.high) * 0.5f32
+    }
+
+    pub fn size(&self) -> Vec3<f32> {
+        self.high - self.low
+    }
+
+    /// Whether `p` is inside or on the surface.
+    pub fn contains(&self, p: &Vec3<f32>) -> bool {
+        p.x >= self.low.x && p.x <= self.high.x &&
+            p.y >= self.low.y && p.y <= self.high.y &&
+            p.z >= self.low.z && p.z <= self.high.z
+    }
+
+    /// How far `p` is from the box; zero inside it.
+    pub fn distance(&self, p: &Vec3<f32>) -> f32 {
+        let outside = |v: f32, low: f32, high: f32| (low - v).max(&(v - high)).max(&0.0);
+        let d = Vec3::new(outside(p.x, self.low.x, self.high.x), outside(p.y, self.low.y, self.high.y),
+                          outside(p.z, self.low.z, self.high.z));
+        d.dot(&d).sqrt()
+    }
+
+    /// Distance along the ray to where it enters the box, or zero if it
+    /// starts inside.
+    pub fn intersect(&self, ray: &Ray) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, f32::infinity);
+        let axes = [(ray.origin.x, ray.dir.x, self.low.x, self.high.x),
+                    (ray.origin.y, ray.dir.y, self.low.y, self.high.y),
+                    (ray.origin.z, ray.dir.z, self.low.z, self.high.z)];
+        for axes.iter().advance |&(origin, dir, low, high)| {
+            if dir == 0.0 {
+                // Parallel to this pair of faces, so it's between them or never in
+                if origin < low || origin > high {
+                    return None;
+                }
+                loop;
+            }
+            let (a, b) = ((low - origin) / dir, (high - origin) / dir);
+            near = near.max(&a.min(&b));
+            far = far.min(&a.max(&b));
+        }
+        if near <= far { Some(near) } else { None }
+    }
+}
+
+/// The points `p` with `normal.dot(p) == offset`, facing along `normal`.
+#[deriving(Clone)]
+pub struct Plane {
+    normal: Vec3<f32>,
+    offset: f32,
+}
+
+impl Plane {
+    /// The plane through `point` facing along `normal`, which needn't be
+    /// normalized.
+    pub fn new(point: &Vec3<f32>, normal: &Vec3<f32>) -> Plane {
+        let normal = normal.normalize();
+        Plane { offset: normal.dot(point), normal: normal }
+    }
+
+    /// Signed distance from the plane, positive on the side it faces.
+    pub fn distance(&self, p: &Vec3<f32>) -> f32 {
+        self.normal.dot(p) - self.offset
+    }
+
+    /// Whether `p` is on the plane or behind it.
+    pub fn contains(&self, p: &Vec3<f32>) -> bool {
+        self.distance(p) <= 0.0
+    }
+
+    /// Distance along the ray to where it crosses the plane, if it does
+    /// ahead of its origin.
+    pub fn intersect(&self, ray: &Ray) -> Option<f32> {
+        let along = self.normal.dot(&ray.dir);
+        if along == 0.0 {
+            return None;
+        }
+        let t = -self.distance(&ray.origin) / along;
+        if t >= 0.0 { Some(t) } else { None }
+    }
+}
+
+/// A solid cylinder with flat ends, from `base` up `axis` for `height`.
+#[deriving(Clone)]
+pub struct Cylinder {
+    base: Vec3<f32>,
+    axis: Vec3<f32>,
+    radius: f32,
+    height: f32,
+}
+
+impl Cylinder {
+    pub fn new(base: Vec3<f32>, axis: Vec3<f32>, radius: f32, height: f32) -> Cylinder {
+        Cylinder { base: base, axis: axis.normalize(), radius: radius, height: height }
+    }
+
+    /// One standing along +y, as rods do.
+    pub fn upright(base: Vec3<f32>, radius: f32, height: f32) -> Cylinder {
+        Cylinder::new(base, Vec3::new(0.0f32, 1.0, 0.0), radius, height)
+    }
+
+    /// `p` relative to the base, split into the height along the axis and
+    /// the part across it.
+    fn split(&self, p: &Vec3<f32>) -> (f32, Vec3<f32>) {
+        let rel = *p - self.base;
+        let along = rel.dot(&self.axis);
+        (along, rel - self.axis * along)
+    }
+
+    pub fn contains(&self, p: &Vec3<f32>) -> bool {
+        let (along, across) = self.split(p);
+        along >= 0.0 && along <= self.height && across.dot(&across) <= self.radius * self.radius
+    }
+
+    /// How far `p` is from the cylinder; zero inside it.
+    pub fn distance(&self, p: &Vec3<f32>) -> f32 {
+        let (along, across) = self.split(p);
+        let out = (across.dot(&across).sqrt() - self.radius).max(&0.0);
+        let up = (-along).max(&(along - self.height)).max(&0.0);
+        (out * out + up * up).sqrt()
+    }
+
+    /// Distance along the ray to where it first hits the side or an end,
+    /// if it does ahead of its origin.
+    pub fn intersect(&self, ray: &Ray) -> Option<f32> {
+        let (origin_along, origin_across) = self.split(&ray.origin);
+        let dir_along = ray.dir.dot(&self.axis);
+        let dir_across = ray.dir - self.axis * dir_along;
+        let r2 = self.radius * self.radius;
+        let mut hits: ~[f32] = ~[];
+
+        // The side, solved across the axis
+        let a = dir_across.dot(&dir_across);
+        let b = 2.0 * origin_across.dot(&dir_across);
+        let c = origin_across.dot(&origin_across) - r2;
+        let discriminant = b * b - 4.0 * a * c;
+        if a > 0.0 && discriminant >= 0.0 {
+            let root = discriminant.sqrt();
+            for [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)].iter().advance |&t| {
+                let along = origin_along + dir_along * t;
+                if along >= 0.0 && along <= self.height {
+                    hits.push(t);
+                }
+            }
+        }
+
+        // The ends
+        if dir_along != 0.0 {
+            for [0.0f32, self.height].iter().advance |&end| {
+                let t = (end - origin_along) / dir_along;
+                let across = origin_across + dir_across * t;
+                if across.dot(&across) <= r2 {
+                    hits.push(t);
+                }
+            }
+        }
+
+        let mut nearest: Option<f32> = None;
+        for hits.iter().advance |&t| {
+            match nearest {
+                Some(n) if n <= t => {},
+                _ if t >= 0.0 => nearest = Some(t),
+                _ => {}
+            }
+        }
+        nearest
+    }
+}
+
+#[test]
+fn test_aabb() {
+    let bounds = Aabb::from_points([Vec3::new(1.0f32, 0.0, 2.0), Vec3::new(-1.0f32, 3.0, 0.0)]).unwrap();
+    assert!(bounds.contains(&Vec3::new(0.0f32, 1.0, 1.0)));
+    assert!(!bounds.contains(&Vec3::new(0.0f32, 4.0, 1.0)));
+    assert!(close(bounds.distance(&Vec3::new(4.0f32, 7.0, 1.0)), 5.0));
+    assert!(close(bounds.distance(&Vec3::new(0.0f32, 1.0, 1.0)), 0.0));
+
+    let ray = Ray::new(Vec3::new(-5.0f32, 1.0, 1.0), Vec3::new(1.0f32, 0.0, 0.0));
+    assert_eq!(bounds.intersect(&ray), Some(4.0));
+    let away = Ray::new(Vec3::new(-5.0f32, 1.0, 1.0), Vec3::new(-1.0f32, 0.0, 0.0));
+    assert_eq!(bounds.intersect(&away), None);
+    let above = Ray::new(Vec3::new(-5.0f32, 4.0, 1.0), Vec3::new(1.0f32, 0.0, 0.0));
+    assert_eq!(bounds.intersect(&above), None);
+    assert!(Aabb::from_points([]).is_none());
+}
+
+#[test]
+fn test_plane() {
+    let plane = Plane::new(&Vec3::new(0.0f32, 2.0, 0.0), &Vec3::new(0.0f32, 3.0, 0.0));
+    assert!(close(plane.distance(&Vec3::new(5.0f32, 5.0, -1.0)), 3.0));
+    assert!(plane.contains(&Vec3::new(0.0f32, 1.0, 0.0)));
+    let down = Ray::new(Vec3::new(1.0f32, 6.0, 1.0), Vec3::new(0.0f32, -2.0, 0.0));
+    assert!(close(plane.intersect(&down).unwrap(), 2.0));
+    let up = Ray::new(Vec3::new(1.0f32, 6.0, 1.0), Vec3::new(0.0f32, 1.0, 0.0));
+    assert_eq!(plane.intersect(&up), None);
+}
+
+#[test]
+fn test_cylinder() {
+    let rod = Cylinder::upright(Vec3::new(1.0f32, 0.0, 1.0), 0.5, 4.0);
+    assert!(rod.contains(&Vec3::new(1.2f32, 3.0, 1.0)));
+    assert!(!rod.contains(&Vec3::new(1.2f32, 5.0, 1.0)));
+    assert!(close(rod.distance(&Vec3::new(4.5f32, 2.0, 1.0)), 3.0));
+    assert!(close(rod.distance(&Vec3::new(1.0f32, -2.0, 1.0)), 2.0));
+
+    // Through the side, then straight down onto the top end
+    let side = Ray::new(Vec3::new(-4.0f32, 2.0, 1.0), Vec3::new(1.0f32, 0.0, 0.0));
+    assert!(close(rod.intersect(&side).unwrap(), 4.5));
+    let top = Ray::new(Vec3::new(1.0f32, 10.0, 1.0), Vec3::new(0.0f32, -1.0, 0.0));
+    assert!(close(rod.intersect(&top).unwrap(), 6.0));
+    let miss = Ray::new(Vec3::new(-4.0f32, 5.0, 1.0), Vec3::new(1.0f32, 0.0, 0.0));
+    assert_eq!(rod.intersect(&miss), None);
+
+    // Tilted along x, the same ray meets the end face instead
+    let lying = Cylinder::new(Vec3::new(0.0f32, 0.0, 0.0), Vec3::new(2.0f32, 0.0, 0.0), 1.0, 3.0);
+    let along = Ray::new(Vec3::new(-2.0f32, 0.5, 0.0), Vec3::new(1.0f32, 0.0, 0.0));
+    assert!(close(lying.intersect(&along).unwrap(), 2.0));
+}
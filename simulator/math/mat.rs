@@ -1,11 +1,14 @@
 
 use std::uint;
 use std::cast::transmute;
-use std::num::{Zero, One, Trigonometric};
+use std::num::{Zero, One, Trigonometric, Algebraic};
 use std::to_str::ToStr;
 
 use math::{Vec3, Vec4};
 
+#[cfg(test)]
+use std::rand::{Rng, IsaacRng};
+
 macro_rules! z(
     ($Type:ty) => (Zero::zero::<$Type>())
 )
@@ -78,9 +81,17 @@ impl<T: Clone> Mat4<T> {
     pub fn col(&self, i: u8) -> Vec4<T> {
         Vec4::new(self.data[i][0].clone(), self.data[i][1].clone(), self.data[i][2].clone(), self.data[i][3].clone())
     }
+
+    pub fn transpose(&self) -> Mat4<T> {
+        let d = &self.data;
+        Mat4::new([d[0][0].clone(), d[1][0].clone(), d[2][0].clone(), d[3][0].clone()],
+                  [d[0][1].clone(), d[1][1].clone(), d[2][1].clone(), d[3][1].clone()],
+                  [d[0][2].clone(), d[1][2].clone(), d[2][2].clone(), d[3][2].clone()],
+                  [d[0][3].clone(), d[1][3].clone(), d[2][3].clone(), d[3][3].clone()])
+    }
 }
 
-impl<T: Mul<T, T> + Add<T, T> + Zero + Clone + ToStr> Mat4<T> {
+impl<T: Mul<T, T> + Add<T, T> + Zero + Clone> Mat4<T> {
     pub fn translate(&self, v: Vec3<T>) -> Mat4<T> {
         let mut result = z!(Mat4<T>);
         result.data[0] = [self.data[0][0].clone(), self.data[0][1].clone(), self.data[0][2].clone(), self.data[0][3].clone()];
@@ -89,28 +100,13 @@ impl<T: Mul<T, T> + Add<T, T> + Zero + Clone + ToStr> Mat4<T> {
         result.data[3] = (self.col(0) * v.x + self.col(1) * v.y + self.col(2) * v.z + self.col(3)).to_vec();
         result
     }
-    pub fn mul_with_log(&self, rhs: &Mat4<T>) -> Mat4<T> {
-        let mut new = z!(Mat4<T>);
-        for uint::range(0, 4) |i| {
-            for uint::range(0, 4) |j| {
-                println(fmt!("column %?, row %?:", i, j));
-                println(fmt!("self.data[%?][0] = %?", i, self.data[i][0]));
-                println(fmt!("self.data[%?][1] = %?", i, self.data[i][1]));
-                println(fmt!("self.data[%?][2] = %?", i, self.data[i][2]));
-                println(fmt!("self.data[%?][3] = %?", i, self.data[i][3]));
-
-                println(fmt!("self.rhs[0][%?] = %?", j, rhs.data[0][j]));
-                println(fmt!("self.rhs[1][%?] = %?", j, rhs.data[1][j]));
-                println(fmt!("self.rhs[2][%?] = %?", j, rhs.data[2][j]));
-                println(fmt!("self.rhs[3][%?] = %?", j, rhs.data[3][j]));
-                new.data[j][i] = self.data[i][0] * rhs.data[0][j] +
-                                 self.data[i][1] * rhs.data[1][j] +
-                                 self.data[i][2] * rhs.data[2][j] + 
-                                 self.data[i][3] * rhs.data[3][j];
-                println(fmt!("%s", new.to_str()));
-            }
-        }
-        new
+    pub fn scale(&self, v: Vec3<T>) -> Mat4<T> {
+        let mut result = z!(Mat4<T>);
+        result.data[0] = (self.col(0) * v.x).to_arr();
+        result.data[1] = (self.col(1) * v.y).to_arr();
+        result.data[2] = (self.col(2) * v.z).to_arr();
+        result.data[3] = [self.data[3][0].clone(), self.data[3][1].clone(), self.data[3][2].clone(), self.data[3][3].clone()];
+        result
     }
 }
 
@@ -142,6 +138,113 @@ impl<T: Mul<T, T> + Add<T, T> + Sub<T, T> + Zero + One + Trigonometric + Clone>
     }
 }
 
+impl<T: Mul<T, T> + Add<T, T> + Sub<T, T> + Div<T, T> + Neg<T> + Zero + Clone> Mat4<T> {
+    /// The inverse by cofactors, or `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Mat4<T>> {
+        let a = &self.data;
+        // 2x2 determinants from the first two and last two columns
+        let s0 = a[0][0] * a[1][1] - a[1][0] * a[0][1];
+        let s1 = a[0][0] * a[1][2] - a[1][0] * a[0][2];
+        let s2 = a[0][0] * a[1][3] - a[1][0] * a[0][3];
+        let s3 = a[0][1] * a[1][2] - a[1][1] * a[0][2];
+        let s4 = a[0][1] * a[1][3] - a[1][1] * a[0][3];
+        let s5 = a[0][2] * a[1][3] - a[1][2] * a[0][3];
+        let c5 = a[2][2] * a[3][3] - a[3][2] * a[2][3];
+        let c4 = a[2][1] * a[3][3] - a[3][1] * a[2][3];
+        let c3 = a[2][1] * a[3][2] - a[3][1] * a[2][2];
+        let c2 = a[2][0] * a[3][3] - a[3][0] * a[2][3];
+        let c1 = a[2][0] * a[3][2] - a[3][0] * a[2][2];
+        let c0 = a[2][0] * a[3][1] - a[3][0] * a[2][1];
+
+        let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if det.is_zero() {
+            return None;
+        }
+
+        let adj = Mat4::new(
+            [a[1][1] * c5 - a[1][2] * c4 + a[1][3] * c3,
+             -a[0][1] * c5 + a[0][2] * c4 - a[0][3] * c3,
+             a[3][1] * s5 - a[3][2] * s4 + a[3][3] * s3,
+             -a[2][1] * s5 + a[2][2] * s4 - a[2][3] * s3],
+            [-a[1][0] * c5 + a[1][2] * c2 - a[1][3] * c1,
+             a[0][0] * c5 - a[0][2] * c2 + a[0][3] * c1,
+             -a[3][0] * s5 + a[3][2] * s2 - a[3][3] * s1,
+             a[2][0] * s5 - a[2][2] * s2 + a[2][3] * s1],
+            [a[1][0] * c4 - a[1][1] * c2 + a[1][3] * c0,
+             -a[0][0] * c4 + a[0][1] * c2 - a[0][3] * c0,
+             a[3][0] * s4 - a[3][1] * s2 + a[3][3] * s0,
+             -a[2][0] * s4 + a[2][1] * s2 - a[2][3] * s0],
+            [-a[1][0] * c3 + a[1][1] * c1 - a[1][2] * c0,
+             a[0][0] * c3 - a[0][1] * c1 + a[0][2] * c0,
+             -a[3][0] * s3 + a[3][1] * s1 - a[3][2] * s0,
+             a[2][0] * s3 - a[2][1] * s1 + a[2][2] * s0]);
+
+        let mut result = z!(Mat4<T>);
+        for uint::range(0, 4) |i| {
+            for uint::range(0, 4) |j| {
+                result.data[i][j] = adj.data[i][j] / det;
+            }
+        }
+        Some(result)
+    }
+}
+
+impl<T: Mul<T, T> + Add<T, T> + Sub<T, T> + Neg<T> + Zero + One + Algebraic + Clone> Mat4<T> {
+    /// A view matrix for an eye at `eye` looking at `center`, as gluLookAt.
+    pub fn look_at(eye: Vec3<T>, center: Vec3<T>, up: Vec3<T>) -> Mat4<T> {
+        let f = (center - eye).normalize();
+        let s = f.cross(&up.normalize()).normalize();
+        let u = s.cross(&f);
+
+        let mut result = Mat4::ident();
+        result.data[0][0] = s.x.clone();
+        result.data[1][0] = s.y.clone();
+        result.data[2][0] = s.z.clone();
+        result.data[0][1] = u.x.clone();
+        result.data[1][1] = u.y.clone();
+        result.data[2][1] = u.z.clone();
+        result.data[0][2] = -f.x;
+        result.data[1][2] = -f.y;
+        result.data[2][2] = -f.z;
+        result.data[3][0] = -s.dot(&eye);
+        result.data[3][1] = -u.dot(&eye);
+        result.data[3][2] = f.dot(&eye);
+        result
+    }
+}
+
+impl<T: Mul<T, T> + Add<T, T> + Sub<T, T> + Div<T, T> + Neg<T> + Zero + One + Trigonometric + Clone> Mat4<T> {
+    /// A perspective projection as gluPerspective, with `fovy` in radians.
+    pub fn perspective(fovy: T, aspect: T, z_near: T, z_far: T) -> Mat4<T> {
+        let one = o!(T);
+        let two = one + one;
+        let tan_half_fovy = (fovy / two).tan();
+
+        let mut result = z!(Mat4<T>);
+        result.data[0][0] = one / (aspect * tan_half_fovy);
+        result.data[1][1] = one / tan_half_fovy;
+        result.data[2][2] = -(z_far + z_near) / (z_far - z_near);
+        result.data[2][3] = -one;
+        result.data[3][2] = -(two * z_far * z_near) / (z_far - z_near);
+        result
+    }
+
+    /// An orthographic projection as glOrtho.
+    pub fn orthographic(left: T, right: T, bottom: T, top: T, z_near: T, z_far: T) -> Mat4<T> {
+        let one = o!(T);
+        let two = one + one;
+
+        let mut result = Mat4::ident();
+        result.data[0][0] = two / (right - left);
+        result.data[1][1] = two / (top - bottom);
+        result.data[2][2] = -two / (z_far - z_near);
+        result.data[3][0] = -(right + left) / (right - left);
+        result.data[3][1] = -(top + bottom) / (top - bottom);
+        result.data[3][2] = -(z_far + z_near) / (z_far - z_near);
+        result
+    }
+}
+
 impl<T: Clone> Index<uint, [T, ..4]> for Mat4<T> {
     fn index(&self, rhs: &uint) -> [T, ..4] {
         [self.data[*rhs][0].clone(), self.data[*rhs][1].clone(), self.data[*rhs][2].clone(), self.data[*rhs][3].clone()]
@@ -216,6 +319,142 @@ fn test_mult() {
                                     [42, 49, 114, 50], [72, 144, 72, 109]));
 }
 
+/// Element by element, tighter than `math::close` since these are f64.
+#[cfg(test)]
+fn close_mat(a: &Mat4<f64>, b: &Mat4<f64>) -> bool {
+    for uint::range(0, 4) |i| {
+        for uint::range(0, 4) |j| {
+            if (a.data[i][j] - b.data[i][j]).abs() > 1e-9 {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+#[test]
+fn test_transpose() {
+    let foo = Mat4::new([1, 2, 3, 4], [5, 6, 7, 8], [9, 10, 11, 12], [13, 14, 15, 16]);
+    assert_eq!(foo.transpose(), Mat4::new([1, 5, 9, 13], [2, 6, 10, 14],
+                                          [3, 7, 11, 15], [4, 8, 12, 16]));
+    assert_eq!(foo.transpose().transpose(), foo);
+}
+
+#[test]
+fn test_inverse() {
+    let scaled: Mat4<f64> = Mat4::ident().translate(Vec3::new(1.0, 2.0, 3.0))
+        .scale(Vec3::new(2.0, 4.0, 8.0));
+    let expected = Mat4::new([0.5, 0.0, 0.0, 0.0], [0.0, 0.25, 0.0, 0.0],
+                             [0.0, 0.0, 0.125, 0.0], [-0.5, -0.5, -0.375, 1.0]);
+    assert_eq!(scaled.inverse(), Some(expected));
+
+    let mat: Mat4<f64> = Mat4::new([5.0, 2.0, 7.0, 1.0], [4.0, 7.0, 6.0, 0.0],
+                                   [2.0, 3.0, 3.0, 2.0], [1.0, 0.0, 4.0, 1.0]);
+    let inverse = mat.inverse().unwrap();
+    assert!(close_mat(&(mat * inverse), &Mat4::ident()));
+    assert!(close_mat(&(inverse * mat), &Mat4::ident()));
+
+    let singular: Mat4<f64> = Mat4::ident().scale(Vec3::new(1.0, 0.0, 1.0));
+    assert!(singular.inverse().is_none());
+}
+
+#[test]
+fn test_look_at() {
+    let view: Mat4<f64> = Mat4::look_at(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, 0.0),
+                                        Vec3::new(0.0, 1.0, 0.0));
+    assert!(close_mat(&view, &Mat4::ident().translate(Vec3::new(0.0, 0.0, -5.0))));
+
+    // Looking down -x, the eye lands on the origin and the center ahead on -z
+    let view: Mat4<f64> = Mat4::look_at(Vec3::new(3.0, 1.0, 2.0), Vec3::new(-1.0, 1.0, 2.0),
+                                        Vec3::new(0.0, 2.0, 0.0));
+    let eye = view.transform(&Vec4::new(3.0, 1.0, 2.0, 1.0));
+    let center = view.transform(&Vec4::new(-1.0, 1.0, 2.0, 1.0));
+    assert!(eye.x.abs() < 1e-9 && eye.y.abs() < 1e-9 && eye.z.abs() < 1e-9);
+    assert!(center.x.abs() < 1e-9 && center.y.abs() < 1e-9 && (center.z + 4.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_perspective() {
+    use std::f64;
+    let proj: Mat4<f64> = Mat4::perspective(f64::consts::pi / 2.0, 2.0, 1.0, 3.0);
+    let expected = Mat4::new([0.5, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0],
+                             [0.0, 0.0, -2.0, -1.0], [0.0, 0.0, -3.0, 0.0]);
+    assert!(close_mat(&proj, &expected));
+}
+
+#[test]
+fn test_orthographic() {
+    let proj: Mat4<f64> = Mat4::orthographic(-2.0, 2.0, -1.0, 1.0, 0.0, 10.0);
+    let expected = Mat4::new([0.5, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0],
+                             [0.0, 0.0, -0.2, 0.0], [0.0, 0.0, -1.0, 1.0]);
+    assert!(close_mat(&proj, &expected));
+    let corner = proj.transform(&Vec4::new(2.0, 1.0, -10.0, 1.0));
+    assert_eq!(corner.to_arr(), [1.0, 1.0, 1.0, 1.0]);
+}
+
+/// A matrix with entries in [-1, 1), for property tests.
+#[cfg(test)]
+fn random_mat(rng: &mut IsaacRng) -> Mat4<f64> {
+    let mut mat = z!(Mat4<f64>);
+    for uint::range(0, 4) |i| {
+        for uint::range(0, 4) |j| {
+            mat.data[i][j] = rng.gen::<f64>() * 2.0 - 1.0;
+        }
+    }
+    mat
+}
+
+#[test]
+fn test_mul_associative() {
+    let mut rng = IsaacRng::new_seeded([4, 2]);
+    for 100.times {
+        let (a, b, c) = (random_mat(&mut rng), random_mat(&mut rng), random_mat(&mut rng));
+        assert!(close_mat(&((a * b) * c), &(a * (b * c))));
+        assert!(close_mat(&(a * Mat4::ident()), &a));
+        assert!(close_mat(&(a * b).transpose(), &(b.transpose() * a.transpose())));
+    }
+}
+
+/// The determinant by expanding along the first column, independently of
+/// the way `inverse` works it out.
+#[cfg(test)]
+fn determinant(mat: &Mat4<f64>) -> f64 {
+    let d = &mat.data;
+    let mut det = 0.0;
+    for uint::range(0, 4) |row| {
+        let rows: ~[uint] = [0u, 1, 2, 3].iter().filter(|&&r| r != row).transform(|&r| r).collect();
+        let m = |col: uint, r: uint| d[col + 1][rows[r]];
+        let minor = m(0, 0) * (m(1, 1) * m(2, 2) - m(2, 1) * m(1, 2)) -
+                    m(1, 0) * (m(0, 1) * m(2, 2) - m(2, 1) * m(0, 2)) +
+                    m(2, 0) * (m(0, 1) * m(1, 2) - m(1, 1) * m(0, 2));
+        let sign = if row % 2 == 0 { 1.0 } else { -1.0 };
+        det += sign * d[0][row] * minor;
+    }
+    det
+}
+
+#[test]
+fn test_inverse_round_trip() {
+    let mut rng = IsaacRng::new_seeded([4, 3]);
+    let mut checked = 0;
+    while checked < 100 {
+        let mat = random_mat(&mut rng);
+        // Nearly singular matrices lose too much to rounding to compare
+        if determinant(&mat).abs() < 0.1 {
+            loop;
+        }
+        match mat.inverse() {
+            Some(inverse) => {
+                assert!(close_mat(&(mat * inverse), &Mat4::ident()));
+                assert!(close_mat(&inverse.inverse().unwrap(), &mat));
+            },
+            None => fail!("%s has no inverse", mat.to_str())
+        }
+        checked += 1;
+    }
+    assert!((determinant(&Mat4::ident()) - 1.0).abs() < 1e-12);
+}
+
 /*
 impl<T: Mul<T, T> + Add<T, T> + Clone> Mul<Vec4<T>, Vec4<T>> for Mat4<T> {
     fn mul(&self, rhs: &Vec4<T>) -> Vec4<T> {
@@ -226,6 +465,16 @@ impl<T: Mul<T, T> + Add<T, T> + Clone> Mul<Vec4<T>, Vec4<T>> for Mat4<T> {
     }
 }
 */
+
+impl<T: Mul<T, T> + Add<T, T> + Clone> Mat4<T> {
+    // Mat4 already implements Mul for matrices, so this can't be a Mul impl
+    pub fn transform(&self, v: &Vec4<T>) -> Vec4<T> {
+        do Vec4::from_fn |i| {
+            self.data[0][i] * v.x + self.data[1][i] * v.y +
+                self.data[2][i] * v.z + self.data[3][i] * v.w
+        }
+    }
+}
 type Mat4f = Mat4<f32>;
 
 
@@ -323,4 +572,28 @@ fn test_trans_inv() {
         [-37.0, -2.0, 27.0, 0.0],
         [0.0, 0.0, 0.0, 0.0]
     )));
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_trans_inv_involution() {
+    let mut rng = IsaacRng::new_seeded([4, 4]);
+    let mut checked = 0;
+    while checked < 100 {
+        let mat = Mat3::from_four(random_mat(&mut rng));
+        let d = &mat.data;
+        let det = d[0][0] * (d[1][1] * d[2][2] - d[2][1] * d[1][2]) -
+                  d[1][0] * (d[0][1] * d[2][2] - d[2][1] * d[0][2]) +
+                  d[2][0] * (d[0][1] * d[1][2] - d[1][1] * d[0][2]);
+        // Nearly singular matrices lose too much to rounding to compare
+        if det.abs() < 0.1 {
+            loop;
+        }
+        let back = mat.trans_inv().trans_inv();
+        for uint::range(0, 3) |i| {
+            for uint::range(0, 3) |j| {
+                assert!((back.data[i][j] - mat.data[i][j]).abs() < 1e-9);
+            }
+        }
+        checked += 1;
+    }
+}
@@ -1,6 +1,21 @@
 
 pub use self::vec::*;
 pub use self::mat::{Mat4, Mat3};
+pub use self::quat::Quat;
+pub use self::geom::{Ray, Aabb, Plane, Cylinder};
 
 mod vec;
 mod mat;
+mod quat;
+mod geom;
+
+/// Whether two f32s are equal but for rounding, for the math tests.
+#[cfg(test)]
+pub fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 0.0001
+}
+
+#[cfg(test)]
+pub fn close_vec(a: &Vec3<f32>, b: &Vec3<f32>) -> bool {
+    close(a.x, b.x) && close(a.y, b.y) && close(a.z, b.z)
+}
@@ -0,0 +1,188 @@
+use math::{Vec3, Mat3, Mat4};
+
+#[cfg(test)]
+use math::{close, close_vec};
+
+/// A rotation as a unit quaternion. Only f32, as that's all the renderer
+/// works in.
+#[deriving(Clone, Eq)]
+pub struct Quat {
+    x: f32,
+    y: f32,
+    z: f32,
+    w: f32,
+}
+
+impl Quat {
+    #[inline(always)]
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Quat {
+        Quat { x: x, y: y, z: z, w: w }
+    }
+
+    /// No rotation.
+    pub fn ident() -> Quat {
+        Quat::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    /// Turns `angle` radians about `axis`, counterclockwise looking down it.
+    pub fn from_axis_angle(axis: Vec3<f32>, angle: f32) -> Quat {
+        let axis = axis.normalize();
+        let s = (angle / 2.0).sin();
+        Quat::new(axis.x * s, axis.y * s, axis.z * s, (angle / 2.0).cos())
+    }
+
+    /// The rotation `Mat4::rotate` gives when called about x, then y, then
+    /// z: turning by `z` first, then `y`, then `x`, all about fixed axes.
+    pub fn from_euler(x: f32, y: f32, z: f32) -> Quat {
+        Quat::from_axis_angle(Vec3::new(1.0f32, 0.0, 0.0), x) *
+            Quat::from_axis_angle(Vec3::new(0.0f32, 1.0, 0.0), y) *
+            Quat::from_axis_angle(Vec3::new(0.0f32, 0.0, 1.0), z)
+    }
+
+    /// Undoes `from_euler`. Angles come back in (-pi, pi], with y in
+    /// [-pi/2, pi/2]; where y is a quarter turn, z is taken as zero.
+    pub fn to_euler(&self) -> Vec3<f32> {
+        let m = self.to_mat3();
+        // data[col][row], so this is row 0, column 2
+        let sin_y = m.data[2][0].max(&-1.0).min(&1.0);
+        if sin_y.abs() > 0.99999 {
+            Vec3::new(m.data[1][2].atan2(&m.data[1][1]), sin_y.asin(), 0.0)
+        } else {
+            Vec3::new((-m.data[2][1]).atan2(&m.data[2][2]), sin_y.asin(),
+                      (-m.data[1][0]).atan2(&m.data[0][0]))
+        }
+    }
+
+    pub fn dot(&self, other: &Quat) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// Scaled back to unit length, which repeated products drift from.
+    pub fn normalize(&self) -> Quat {
+        let length = self.length();
+        if length > 0.0 {
+            Quat::new(self.x / length, self.y / length, self.z / length, self.w / length)
+        } else {
+            Quat::ident()
+        }
+    }
+
+    /// The opposite rotation, for unit quaternions.
+    pub fn conjugate(&self) -> Quat {
+        Quat::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    pub fn rotate(&self, v: &Vec3<f32>) -> Vec3<f32> {
+        // v + 2w(q x v) + 2q x (q x v), which skips building a matrix
+        let q = Vec3::new(self.x, self.y, self.z);
+        let t = q.cross(v) * 2.0f32;
+        *v + t * self.w + q.cross(&t)
+    }
+
+    /// Spherical interpolation from `self` at `t` = 0 to `other` at 1, at a
+    /// constant angular speed and the short way around.
+    pub fn slerp(&self, other: &Quat, t: f32) -> Quat {
+        let mut cos = self.dot(other);
+        // q and -q are the same rotation; pick whichever is closer
+        let other = if cos < 0.0 {
+            cos = -cos;
+            Quat::new(-other.x, -other.y, -other.z, -other.w)
+        } else {
+            other.clone()
+        };
+        let (a, b) = if cos > 0.9995 {
+            // Close enough that sin(angle) is mostly rounding error
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Quat::new(self.x * a + other.x * b, self.y * a + other.y * b,
+                  self.z * a + other.z * b, self.w * a + other.w * b).normalize()
+    }
+
+    pub fn to_mat3(&self) -> Mat3<f32> {
+        let (x, y, z, w) = (self.x, self.y, self.z, self.w);
+        Mat3 { data: [
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + z * w), 2.0 * (x * z - y * w)],
+            [2.0 * (x * y - z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + x * w)],
+            [2.0 * (x * z + y * w), 2.0 * (y * z - x * w), 1.0 - 2.0 * (x * x + y * y)],
+        ]}
+    }
+
+    pub fn to_mat4(&self) -> Mat4<f32> {
+        let m = self.to_mat3().data;
+        Mat4::new([m[0][0], m[0][1], m[0][2], 0.0],
+                  [m[1][0], m[1][1], m[1][2], 0.0],
+                  [m[2][0], m[2][1], m[2][2], 0.0],
+                  [0.0, 0.0, 0.0, 1.0])
+    }
+}
+
+/// Composes rotations: `a * b` turns by `b` first, then `a`.
+impl Mul<Quat, Quat> for Quat {
+    fn mul(&self, rhs: &Quat) -> Quat {
+        Quat::new(self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+                  self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+                  self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+                  self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z)
+    }
+}
+
+#[test]
+fn test_rotate() {
+    use std::f32;
+    let quarter = Quat::from_axis_angle(Vec3::new(0.0f32, 0.0, 1.0), f32::consts::pi / 2.0);
+    let turned = quarter.rotate(&Vec3::new(1.0f32, 0.0, 0.0));
+    assert!(close_vec(&turned, &Vec3::new(0.0f32, 1.0, 0.0)));
+    let back = quarter.conjugate().rotate(&turned);
+    assert!(close_vec(&back, &Vec3::new(1.0f32, 0.0, 0.0)));
+}
+
+#[test]
+fn test_euler_matches_mat4_rotate() {
+    use std::uint;
+    let (x, y, z) = (0.3f32, -1.1, 2.0);
+    let mut mat = Mat4::ident();
+    mat = mat.rotate(x, Vec3::new(1.0f32, 0.0, 0.0));
+    mat = mat.rotate(y, Vec3::new(0.0f32, 1.0, 0.0));
+    mat = mat.rotate(z, Vec3::new(0.0f32, 0.0, 1.0));
+    let quat = Quat::from_euler(x, y, z).to_mat4();
+    for uint::range(0, 4) |i| {
+        for uint::range(0, 4) |j| {
+            assert!(close(mat.data[i][j], quat.data[i][j]));
+        }
+    }
+    let angles = Quat::from_euler(x, y, z).to_euler();
+    assert!(close_vec(&angles, &Vec3::new(x, y, z)));
+}
+
+#[test]
+fn test_mat_matches_rotate() {
+    let quat = Quat::from_axis_angle(Vec3::new(1.0f32, 2.0, -0.5), 0.8);
+    let v = Vec3::new(0.4f32, -0.2, 1.5);
+    let m = quat.to_mat3().data;
+    let by_mat = Vec3::new(m[0][0] * v.x + m[1][0] * v.y + m[2][0] * v.z,
+                           m[0][1] * v.x + m[1][1] * v.y + m[2][1] * v.z,
+                           m[0][2] * v.x + m[1][2] * v.y + m[2][2] * v.z);
+    assert!(close_vec(&by_mat, &quat.rotate(&v)));
+}
+
+#[test]
+fn test_slerp() {
+    let axis = Vec3::new(0.0f32, 1.0, 0.0);
+    let a = Quat::from_axis_angle(axis.clone(), 0.2);
+    let b = Quat::from_axis_angle(axis.clone(), 1.4);
+    let mid = a.slerp(&b, 0.5);
+    let expected = Quat::from_axis_angle(axis.clone(), 0.8);
+    assert!(close(mid.dot(&expected).abs(), 1.0));
+    assert!(close(a.slerp(&b, 0.0).dot(&a), 1.0));
+    // The negated end is the same rotation, so the path shouldn't change
+    let negated = Quat::new(-b.x, -b.y, -b.z, -b.w);
+    assert!(close(a.slerp(&negated, 0.5).dot(&expected).abs(), 1.0));
+}
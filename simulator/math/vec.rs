@@ -2,6 +2,9 @@
 use std::num::One;
 use std::cast::transmute;
 
+#[cfg(test)]
+use std::rand::{Rng, IsaacRng};
+
 pub struct Vec4<T> {
     x: T,
     y: T,
@@ -165,4 +168,37 @@ impl<T: Mul<T, T>> Mul<T, Vec3<T>> for Vec3<T> {
     }
 }
 
-pub type Vec3f = Vec3<f32>;
\ No newline at end of file
+pub type Vec3f = Vec3<f32>;
+
+/// A vector with components in [-10, 10), for property tests.
+#[cfg(test)]
+fn random_vec3(rng: &mut IsaacRng) -> Vec3<f64> {
+    Vec3::new(rng.gen::<f64>() * 20.0 - 10.0, rng.gen::<f64>() * 20.0 - 10.0,
+              rng.gen::<f64>() * 20.0 - 10.0)
+}
+
+#[test]
+fn test_normalize_unit_length() {
+    let mut rng = IsaacRng::new_seeded([3, 1]);
+    for 100.times {
+        let v = random_vec3(&mut rng);
+        let n = v.normalize();
+        assert!((n.dot(&n) - 1.0).abs() < 1e-9);
+        // Same direction, so the cross product vanishes
+        let cross = n.cross(&v);
+        assert!(cross.dot(&cross) < 1e-9);
+    }
+}
+
+#[test]
+fn test_cross_orthogonal() {
+    let mut rng = IsaacRng::new_seeded([3, 2]);
+    for 100.times {
+        let (a, b) = (random_vec3(&mut rng), random_vec3(&mut rng));
+        let cross = a.cross(&b);
+        assert!(cross.dot(&a).abs() < 1e-9);
+        assert!(cross.dot(&b).abs() < 1e-9);
+        let back = b.cross(&a);
+        assert!((cross + back).dot(&(cross + back)) < 1e-9);
+    }
+}
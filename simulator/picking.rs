@@ -0,0 +1,26 @@
+use math::{Ray, Cylinder};
+use gl::obj::ROD_RADIUS;
+use layout::Rod;
+
+/// Rods are thin, so clicks get some slack around them.
+static PICK_SLACK: f32 = 1.5;
+
+/// The index of the nearest rod under the ray, if any.
+pub fn pick_rod(ray: &Ray, rods: &[Rod]) -> Option<uint> {
+    let mut best: Option<(f32, uint)> = None;
+    for rods.iter().advance |rod| {
+        let height = rod.top().y - rod.base().y;
+        let cylinder = Cylinder::upright(rod.base(), ROD_RADIUS * PICK_SLACK, height);
+        match cylinder.intersect(ray) {
+            Some(t) => match best {
+                Some((best_t, _)) if best_t <= t => {},
+                _ => best = Some((t, rod.index))
+            },
+            None => {}
+        }
+    }
+    match best {
+        Some((_, index)) => Some(index),
+        None => None
+    }
+}
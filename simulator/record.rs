@@ -0,0 +1,145 @@
+use std::{io, os, uint, vec};
+use std::libc::c_int;
+
+use gl::camera::Camera;
+use bookmarks::Flythrough;
+use show::ShowEvent;
+
+use es = opengles::gl2;
+
+pub struct Settings {
+    output: ~str,
+    fps: uint,
+    duration: Option<float>,
+}
+
+pub enum FrameSink {
+    Y4mStream(@io::Writer),
+    ImageSequence(~str),
+}
+
+impl FrameSink {
+    /// Outputs ending in `.y4m` get a single raw video stream; anything else
+    /// is a directory to fill with numbered PPM images.
+    pub fn create(output: &str, width: uint, height: uint, fps: uint) -> Result<FrameSink, ~str> {
+        if output.ends_with(".y4m") {
+            let writer = match io::file_writer(&Path(output), [io::Create, io::Truncate]) {
+                Ok(writer) => writer,
+                Err(error) => return Err(error)
+            };
+            writer.write_str(fmt!("YUV4MPEG2 W%u H%u F%u:1 Ip A1:1 C444\n", width, height, fps));
+            Ok(Y4mStream(writer))
+        } else {
+            let dir = Path(output);
+            if !os::path_is_dir(&dir) && !os::make_dir(&dir, 0x1ed as c_int /* rwxr-xr-x */) {
+                return Err(fmt!("Couldn't create directory %s", output));
+            }
+            Ok(ImageSequence(output.to_owned()))
+        }
+    }
+
+    /// Takes rows bottom to top, the way OpenGL reads them back.
+    pub fn write_frame(&self, frame: uint, width: uint, height: uint, pixels: &[u8]) -> Result<(), ~str> {
+        match *self {
+            Y4mStream(writer) => {
+                let mut planes = [vec::with_capacity(width * height),
+                                  vec::with_capacity(width * height),
+                                  vec::with_capacity(width * height)];
+                for uint::range(0, height) |n| {
+                    let start = (height - 1 - n) * width * 3;
+                    for uint::range(0, width) |col| {
+                        let i = start + col * 3;
+                        let (y, cb, cr) = rgb_to_ycbcr(pixels[i], pixels[i + 1], pixels[i + 2]);
+                        planes[0].push(y);
+                        planes[1].push(cb);
+                        planes[2].push(cr);
+                    }
+                }
+                writer.write_str("FRAME\n");
+                for planes.iter().advance |plane| {
+                    writer.write(*plane);
+                }
+                Ok(())
+            },
+            ImageSequence(ref dir) => {
+                let path = fmt!("%s/%05u.ppm", *dir, frame);
+                let writer = match io::file_writer(&Path(path), [io::Create, io::Truncate]) {
+                    Ok(writer) => writer,
+                    Err(error) => return Err(error)
+                };
+                writer.write_str(fmt!("P6\n%u %u\n255\n", width, height));
+                for uint::range(0, height) |n| {
+                    let row = height - 1 - n;
+                    writer.write(pixels.slice(row * width * 3, (row + 1) * width * 3));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// BT.601 studio range, which is what players assume for Y4M.
+fn rgb_to_ycbcr(r: u8, g: u8, b: u8) -> (u8, u8, u8) {
+    let (r, g, b) = (r as float, g as float, b as float);
+    let y = 16.0 + (65.738 * r + 129.057 * g + 25.064 * b) / 256.0;
+    let cb = 128.0 + (-37.945 * r - 74.494 * g + 112.439 * b) / 256.0;
+    let cr = 128.0 + (112.439 * r - 94.154 * g - 18.285 * b) / 256.0;
+    (y.round() as u8, cb.round() as u8, cr.round() as u8)
+}
+
+pub fn read_frame(width: uint, height: uint) -> ~[u8] {
+    check!(es::pixel_store_i(es::PACK_ALIGNMENT, 1));
+    check!(es::read_pixels(0, 0, width as es::GLsizei, height as es::GLsizei, es::RGB, es::UNSIGNED_BYTE))
+}
+
+/// Renders `events` frame by frame on a fixed timestep. Nothing here looks
+/// at the clock, so the same show always produces the same frames however
+/// fast the machine renders them. Returns the number of frames written.
+pub fn record(camera: &mut Camera, events: &[ShowEvent], flythrough: Option<&Flythrough>,
+              settings: &Settings) -> Result<uint, ~str> {
+    let (width, height) = (camera.width as uint, camera.height as uint);
+    let sink = match FrameSink::create(settings.output, width, height, settings.fps) {
+        Ok(sink) => sink,
+        Err(error) => return Err(error)
+    };
+
+    let duration = match settings.duration {
+        Some(duration) => duration,
+        None => {
+            let show_end = if events.len() > 0 { events[events.len() - 1].time + 1.0 } else { 0.0 };
+            let path_end = match flythrough { Some(f) => f.duration() as float, None => 0.0 };
+            show_end.max(&path_end)
+        }
+    };
+    let frames = (duration * settings.fps as float).ceil() as uint;
+
+    let mut next_event = 0;
+    for uint::range(0, frames) |frame| {
+        let time = (frame as float) / (settings.fps as float);
+        while next_event < events.len() && events[next_event].time <= time {
+            let event = &events[next_event];
+            match camera.rod_mut(event.led) {
+                Some(rod) => {
+                    let (r, g, b) = event.rgb;
+                    rod.set_rgb(r, g, b);
+                },
+                None => {}
+            }
+            next_event += 1;
+        }
+        match flythrough {
+            Some(f) => match f.pose_at(time as f32) {
+                Some(pose) => camera.set_pose(&pose),
+                None => {}
+            },
+            None => {}
+        }
+
+        camera.render();
+        match sink.write_frame(frame, width, height, read_frame(width, height)) {
+            Ok(()) => {},
+            Err(error) => return Err(error)
+        }
+    }
+    Ok(frames)
+}
@@ -0,0 +1,154 @@
+use std::{io, comm, task, uint, rand};
+use std::hashmap::HashMap;
+use extra::net;
+use extra::time;
+use extra::uv_global_loop;
+
+use show;
+use show::ShowEvent;
+
+/// Sent from connection tasks so the main task can show who's connected.
+pub enum ClientEvent {
+    Connected(uint, ~str),
+    Received(uint),
+    Disconnected(uint),
+}
+
+pub struct ClientStats {
+    addr: ~str,
+    last_seen: float,
+    rate: float,
+    window_start: float,
+    window_messages: uint,
+}
+
+/// The connected clients, as far as the main task has heard.
+pub struct Clients {
+    port: comm::Port<ClientEvent>,
+    clients: HashMap<uint, ClientStats>,
+}
+
+/// How long message rates are averaged over, in seconds.
+static RATE_WINDOW: float = 1.0;
+
+impl Clients {
+    /// Catches up on events from the connection tasks. Call once a frame.
+    pub fn update(&mut self) {
+        let now = time::precise_time_s();
+        while self.port.peek() {
+            match self.port.recv() {
+                Connected(id, addr) => {
+                    self.clients.insert(id, ClientStats {
+                        addr: addr, last_seen: now, rate: 0.0, window_start: now, window_messages: 0
+                    });
+                },
+                Received(id) => match self.clients.find_mut(&id) {
+                    Some(stats) => {
+                        stats.last_seen = now;
+                        stats.window_messages += 1;
+                    },
+                    None => {}
+                },
+                Disconnected(id) => { self.clients.remove(&id); }
+            }
+        }
+        for self.clients.mut_iter().advance |(_, stats)| {
+            if now - stats.window_start >= RATE_WINDOW {
+                stats.rate = (stats.window_messages as float) / (now - stats.window_start);
+                stats.window_start = now;
+                stats.window_messages = 0;
+            }
+        }
+    }
+}
+
+/// Listens for LED messages on port 7654 and returns one port per LED that
+/// yields its new colors, plus the stream of client activity. With
+/// `capture`, every message is also saved to that file as a show.
+pub fn start_server(num_leds: u8, capture: Option<~str>) -> (~[comm::Port<(u8, u8, u8)>], Clients) {
+    let mut ports: ~[comm::Port<(u8, u8, u8)>] = ~[];
+    let mut chans: ~[comm::SharedChan<(u8, u8, u8)>] = ~[];
+    for (num_leds as uint).times {
+        let (port, chan) = comm::stream();
+        ports.push(port);
+        chans.push(comm::SharedChan::new(chan));
+    }
+    let chans = chans;
+
+    let (client_port, client_chan) = comm::stream::<ClientEvent>();
+    let client_chan = comm::SharedChan::new(client_chan);
+
+    // A single task owns the capture file so connections don't interleave
+    // partial lines
+    let start_time = time::precise_time_s();
+    let capture_chan = match capture {
+        Some(path) => {
+            let (port, chan) = comm::stream::<ShowEvent>();
+            do task::spawn {
+                let writer = match io::file_writer(&Path(path), [io::Create, io::Truncate]) {
+                    Ok(writer) => writer,
+                    Err(error) => fail!(error)
+                };
+                loop {
+                    match port.try_recv() {
+                        Some(event) => writer.write_line(show::format_event(&event)),
+                        None => break
+                    }
+                }
+            }
+            Some(comm::SharedChan::new(chan))
+        },
+        None => None
+    };
+
+    let iotask = uv_global_loop::get();
+    let localhost = net::ip::v4::parse_addr("127.0.0.1");
+
+    do task::spawn {
+        let cloned_chans = chans.clone();
+        do net::tcp::listen(localhost, 7654, 100, &iotask, |_| ()) |new_conn, _| {
+            let my_chans = cloned_chans.clone();
+            let my_capture = capture_chan.clone();
+            let my_clients = client_chan.clone();
+            do task::spawn {
+                let accept_result = net::tcp::accept(new_conn);
+                let sock = match accept_result {
+                    Ok(val) => val,
+                    Err(_) => {
+                        fail!("Socket error")
+                    }
+                };
+                let addr = net::ip::format_addr(&sock.get_peer_addr());
+                printfln!("Connection from %s", addr);
+                // Only needs to tell connections apart while they're open
+                let id = rand::random::<uint>();
+                my_clients.send(Connected(id, addr));
+                let sock_buf = net::tcp::socket_buf(sock);
+                loop {
+                    let leds = sock_buf.read_byte();
+                    if leds < 0 {
+                        break;
+                    }
+                    for uint::range(0, leds as uint) |_| {
+                        let bytes = sock_buf.read_bytes(4);
+                        let led = bytes[0];
+                        if led < num_leds {
+                            my_chans[led].send((bytes[1], bytes[2], bytes[3]));
+                            match my_capture {
+                                Some(ref chan) => chan.send(ShowEvent {
+                                    time: time::precise_time_s() - start_time,
+                                    led: led as uint,
+                                    rgb: (bytes[1], bytes[2], bytes[3]),
+                                }),
+                                None => {}
+                            }
+                        }
+                    }
+                    my_clients.send(Received(id));
+                }
+                my_clients.send(Disconnected(id));
+            }
+        };
+    }
+    return (ports, Clients { port: client_port, clients: HashMap::new() });
+}
@@ -1,7 +1,12 @@
 #version 150
 in vec4 f_color;
+in vec3 f_texcoord;
+uniform float highlight, textured;
+uniform sampler2D diffuse_map;
 out vec4 frag_color;
 
 void main() {
-    frag_color = f_color;
+    vec4 base = f_color * mix(vec4(1.0), texture(diffuse_map, f_texcoord.st), textured);
+    // Selected meshes are washed toward opaque white
+    frag_color = mix(base, vec4(1.0, 1.0, 1.0, 1.0), 0.5 * highlight);
 }
\ No newline at end of file
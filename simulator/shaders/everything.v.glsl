@@ -2,9 +2,11 @@
 in vec4 v_coord;
 in vec3 v_normal;
 in vec4 v_color;
+in vec3 v_texcoord;
 uniform mat4 m, v, p, m_orig;
 uniform mat3 m_inv_transp;
 out vec4 f_color;
+out vec3 f_texcoord;
 
 void main(void)
 {
@@ -14,5 +16,6 @@ void main(void)
     mvp = p*v*m;
 
     f_color = v_color;
+    f_texcoord = v_texcoord;
     gl_Position = mvp * v_coord, 1.0;
 }
@@ -0,0 +1,17 @@
+#version 150
+in vec4 f_color;
+in vec3 f_normal;
+in vec3 f_texcoord;
+uniform float highlight, textured;
+uniform sampler2D diffuse_map;
+out vec4 frag_color;
+
+void main() {
+    vec4 base = f_color * mix(vec4(1.0), texture(diffuse_map, f_texcoord.st), textured);
+    // Brightest where the surface turns away from the viewer, like light
+    // scattering out of the edges of the acrylic
+    float rim = 1.0 - abs(normalize(f_normal).z);
+    vec3 glow = base.rgb * (0.8 + 1.2 * rim * rim) + 0.1 * rim;
+    vec4 color = vec4(glow, min(base.a + 0.3 * rim, 1.0));
+    frag_color = mix(color, vec4(1.0, 1.0, 1.0, 1.0), 0.5 * highlight);
+}
\ No newline at end of file
@@ -0,0 +1,18 @@
+#version 150
+in vec4 v_coord;
+in vec3 v_normal;
+in vec4 v_color;
+in vec3 v_texcoord;
+uniform mat4 m, v, p;
+uniform mat3 m_inv_transp;
+out vec4 f_color;
+out vec3 f_texcoord;
+out vec3 f_normal;
+
+void main(void)
+{
+    f_color = v_color;
+    f_texcoord = v_texcoord;
+    f_normal = mat3(v) * m_inv_transp * v_normal;
+    gl_Position = p * v * m * v_coord;
+}
\ No newline at end of file
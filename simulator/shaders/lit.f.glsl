@@ -0,0 +1,26 @@
+#version 150
+in vec4 f_color;
+in vec3 f_normal;
+in vec3 f_position;
+in vec3 f_texcoord;
+uniform mat4 v;
+uniform float highlight, textured, shininess;
+uniform vec3 specular;
+uniform sampler2D diffuse_map;
+out vec4 frag_color;
+
+void main() {
+    vec4 base = f_color * mix(vec4(1.0), texture(diffuse_map, f_texcoord.st), textured);
+    // A single light from above and in front of the sculpture
+    vec3 light = normalize(mat3(v) * vec3(0.3, 1.0, 0.5));
+    vec3 normal = normalize(f_normal);
+    float diffuse = abs(dot(normal, light));
+    // Blinn-Phong highlights, on whichever side faces the viewer
+    vec3 to_eye = normalize(-f_position);
+    if (dot(normal, to_eye) < 0.0) {
+        normal = -normal;
+    }
+    float shine = shininess > 0.0 ? pow(max(dot(normal, normalize(light + to_eye)), 0.0), shininess) : 0.0;
+    vec4 color = vec4(base.rgb * (0.35 + 0.65 * diffuse) + specular * shine, base.a);
+    frag_color = mix(color, vec4(1.0, 1.0, 1.0, 1.0), 0.5 * highlight);
+}
\ No newline at end of file
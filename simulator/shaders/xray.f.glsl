@@ -0,0 +1,15 @@
+#version 150
+in vec4 f_color;
+in vec3 f_normal;
+in vec3 f_texcoord;
+uniform float highlight, textured;
+uniform sampler2D diffuse_map;
+out vec4 frag_color;
+
+void main() {
+    vec4 base = f_color * mix(vec4(1.0), texture(diffuse_map, f_texcoord.st), textured);
+    // Only the silhouettes stay solid, so everything behind shows through
+    float rim = 1.0 - abs(normalize(f_normal).z);
+    vec4 color = vec4(base.rgb, 0.1 + 0.9 * rim * rim);
+    frag_color = mix(color, vec4(1.0, 1.0, 1.0, 1.0), 0.5 * highlight);
+}
\ No newline at end of file
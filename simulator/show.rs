@@ -0,0 +1,91 @@
+use std::io;
+use std::{float, uint, u8};
+use extra::sort;
+
+/// One color change in a show, `time` seconds after it starts.
+pub struct ShowEvent {
+    time: float,
+    led: uint,
+    rgb: (u8, u8, u8),
+}
+
+/// Shows are plain text with one event per line, `time led r g b`, and
+/// blank lines or lines starting with `#` ignored. This is also the format
+/// the server records incoming messages in.
+pub fn load_show(path_str: &str) -> Result<~[ShowEvent], ~str> {
+    match io::file_reader(&Path(path_str)) {
+        Ok(reader) => read_show(reader, path_str),
+        Err(error) => Err(error)
+    }
+}
+
+/// Reads a show from `reader`, naming `source` in errors.
+fn read_show(reader: @io::Reader, source: &str) -> Result<~[ShowEvent], ~str> {
+    let mut events = ~[];
+    let mut line_num = 0u;
+    let mut error = None;
+    for reader.each_line |line| {
+        line_num += 1;
+        let line = line.trim();
+        if line.len() == 0 || line.starts_with("#") {
+            loop;
+        }
+        match parse_event(line) {
+            Some(event) => events.push(event),
+            None => {
+                error = Some(fmt!("%s:%u: expected \"time led r g b\"", source, line_num));
+                break;
+            }
+        }
+    }
+    match error {
+        Some(error) => return Err(error),
+        None => {}
+    }
+
+    // Hand-written shows don't have to be in order
+    Ok(sort::merge_sort(events, |a, b| a.time <= b.time))
+}
+
+fn parse_event(line: &str) -> Option<ShowEvent> {
+    let words: ~[&str] = line.word_iter().collect();
+    if words.len() != 5 {
+        return None;
+    }
+    match (float::from_str(words[0]), uint::from_str_radix(words[1], 10), u8::from_str_radix(words[2], 10),
+           u8::from_str_radix(words[3], 10), u8::from_str_radix(words[4], 10)) {
+        (Some(time), Some(led), Some(r), Some(g), Some(b)) if time >= 0.0 => {
+            Some(ShowEvent { time: time, led: led, rgb: (r, g, b) })
+        },
+        _ => None
+    }
+}
+
+pub fn format_event(event: &ShowEvent) -> ~str {
+    let (r, g, b) = event.rgb;
+    fmt!("%.4f %u %u %u %u", event.time, event.led, r as uint, g as uint, b as uint)
+}
+
+#[test]
+fn test_round_trip() {
+    let events = [ShowEvent { time: 0.0, led: 3, rgb: (255, 0, 7) },
+                  ShowEvent { time: 1.25, led: 0, rgb: (0, 0, 0) },
+                  ShowEvent { time: 12.0625, led: 13, rgb: (1, 128, 255) }];
+    // Out of order, with a comment and a blank line, as a hand-written show
+    let text = fmt!("# captured\n%s\n\n%s\n%s\n", format_event(&events[2]), format_event(&events[0]),
+                    format_event(&events[1]));
+    let read = match do io::with_str_reader(text) |reader| { read_show(reader, "show.txt") } {
+        Ok(read) => read,
+        Err(error) => fail!(error)
+    };
+    assert_eq!(read.len(), 3);
+    for read.iter().zip(events.iter()).advance |(a, b)| {
+        assert!(a.time == b.time && a.led == b.led && a.rgb == b.rgb);
+    }
+
+    let text = fmt!("%s\n\n0.5 2 255 0\n", format_event(&events[0]));
+    match do io::with_str_reader(text) |reader| { read_show(reader, "show.txt") } {
+        Ok(_) => fail!("read an event with no blue"),
+        Err(error) => assert_eq!(error, ~"show.txt:3: expected \"time led r g b\"")
+    }
+}
@@ -2,19 +2,25 @@
 extern mod glfw;
 extern mod opengles;
 extern mod extra;
+extern mod lightsculpture;
 
 use std::io;
 use std::os;
-use std::comm;
-use std::task;
 use std::uint;
-use extra::json;
-use extra::net;
-use extra::uv_global_loop;
+use std::vec;
+use std::float;
+use std::libc::c_int;
+use extra::getopts::*;
 
 use math::Vec3;
-use gl::camera::Camera;
+use gl::camera::{Camera, ViewLayout, Single, Perspective};
 use gl::obj::Mesh;
+use gl::shader::{ShaderSource, Files};
+use bookmarks::{Bookmarks, Flythrough};
+use hud::Hud;
+use led::LedResponse;
+use lightsculpture::effects;
+use lightsculpture::effects::Position;
 
 use es = opengles::gl2;
 
@@ -27,110 +33,387 @@ mod gl;
 #[macro_escape]
 mod check;
 
+mod config;
+mod bookmarks;
+mod show;
+mod record;
+mod layout;
+mod server;
+mod hud;
+mod picking;
+mod led;
+mod export;
+mod assets;
+
 static SCREEN_WIDTH: uint = 800;
 static SCREEN_HEIGHT: uint = 600;
+static BOOKMARKS_FILE: &'static str = "bookmarks.json";
+
+/// Shaders selectable with F1 onwards, by the name shown and the name of
+/// their files.
+static SHADER_PRESETS: [(&'static str, &'static str), ..4] = [
+    ("flat", "everything"),
+    ("lit", "lit"),
+    ("glow", "glow"),
+    ("x-ray", "xray"),
+];
+
+fn print_usage(program: &str) {
+    printfln!("Usage: %s [options] [rods.json]", program);
+    println("");
+    println("  --capture FILE    record incoming messages to FILE as a show");
+    println("  --record OUTPUT   render --show offline instead of opening a window;");
+    println("                    OUTPUT is a .y4m file or a directory for PPM frames");
+    println("  --show FILE       show to play while recording");
+    println("  --fps N           frames per second to record (default 30)");
+    println("  --duration SECS   length to record (default: until the show ends)");
+    println("  --path NAME       camera path from bookmarks.json to fly while recording");
+    println("  --size WxH        window or recording size (default 800x600)");
+    println("  --leds FILE       LED response model to color the rods with");
+    println("  --view VIEW       perspective, plan, elevation or split (default perspective)");
+    println("  --base FILE       glTF, GLB or OBJ model in layout units to use as the base");
+    println("  --model FILE      glTF, GLB or OBJ model in layout units to show with the rods;");
+    println("                    repeatable");
+    println("  --export FILE     write the base, models and rods to an .stl or .obj file");
+    println("                    and exit without opening a window");
+    println("  --export-scale N  size of a layout unit in the exported file (default 1)");
+    println("  --shaders DIR     load shaders from DIR instead of the built-in ones, and");
+    println("                    reload them when they change");
+    println("  --rod-mesh FILE   OBJ cylinder of unit radius and height to cut rods from");
+    printfln!("  --effect NAME     run a built-in effect instead of listening for clients: %s",
+              effects::NAMES.connect(", "));
+}
+
+/// The shader `file_name`, from `shader_dir` if one was given so it can be
+/// edited, otherwise built in.
+fn shader_source(shader_dir: &Option<~str>, file_name: &str) -> ShaderSource {
+    let required = match assets::required_inputs(file_name) {
+        Some(required) => required,
+        None => fail!(fmt!("No built-in shader named %s", file_name))
+    };
+    match *shader_dir {
+        Some(ref dir) => Files(Path(*dir).push(file_name).to_str(), required),
+        None => assets::shader(file_name).unwrap()
+    }
+}
+
+/// The base, any models, and a mesh per rod in layout order, all in scene
+/// units and named for export.
+fn build_scene(matches: &Matches, rods: &[layout::Rod], response: &LedResponse) -> ~[Mesh] {
+    let mut meshes = ~[];
+    let mut models = ~[];
+    match opt_maybe_str(matches, "base") {
+        Some(base_file) => match Mesh::load_model_file(base_file) {
+            Ok(base) => models.push_all_move(base),
+            Err(error) => fail!(error)
+        },
+        None => {
+            let mut base = Mesh::gen_base();
+            base.name = Some(~"base");
+            meshes.push(base);
+        }
+    }
+    for opt_strs(matches, "model").iter().advance |model_file| {
+        match Mesh::load_model_file(*model_file) {
+            Ok(loaded) => models.push_all_move(loaded),
+            Err(error) => fail!(error)
+        }
+    }
+    for models.consume_iter().advance |model| {
+        let mut model = model;
+        model.scale(layout::SCENE_SCALE);
+        meshes.push(model);
+    }
+    
+    let unit_rod = match opt_maybe_str(matches, "rod-mesh") {
+        Some(mesh_file) => Mesh::load_from_obj_file(mesh_file),
+        // The built-in one names no materials, so nothing is looked for
+        // next to the binary
+        None => do io::with_str_reader(assets::ROD_MESH) |reader| {
+            Mesh::load_from_obj(reader, "cylinder.obj")
+        }
+    };
+    let unit_rod = match unit_rod {
+        Ok(mesh) => mesh,
+        Err(error) => fail!(error)
+    };
+    for rods.iter().advance |spec| {
+        let mut rod = do Mesh::better_rod(&unit_rod, spec.index,
+                                          spec.x * layout::SCENE_SCALE,
+                                          spec.y * layout::SCENE_SCALE,
+                                          spec.height * layout::SCENE_SCALE,
+                                          response.clone()) |along| {
+            spec.diffusion.brightness(along * spec.height, spec.height)
+        };
+        rod.name = Some(fmt!("rod %s", spec.label()));
+        meshes.push(rod);
+    }
+    meshes
+}
 
 fn main() {
     #[main];
     
+    let args = os::args();
+    let opts = ~[optopt("capture"), optopt("record"), optopt("show"), optopt("fps"),
+                 optopt("duration"), optopt("path"), optopt("size"), optopt("leds"), optopt("view"),
+                 optopt("base"), optmulti("model"), optopt("export"), optopt("export-scale"),
+                 optopt("shaders"), optopt("rod-mesh"), optopt("effect"), optflag("help")];
+    let matches = match getopts(args.tail(), opts) {
+        Ok(matches) => matches,
+        Err(f) => fail!(fail_str(f))
+    };
+    if opt_present(&matches, "help") {
+        print_usage(args[0]);
+        return;
+    }
+    
+    let rods = match matches.free.len() {
+        0 => layout::default_layout(),
+        1 => layout::load_layout(matches.free[0]),
+        _ => fail!("Only one rods file can be given")
+    };
+    let rods = match rods {
+        Ok(rods) => rods,
+        Err(error) => fail!(error)
+    };
+    let response = match opt_maybe_str(&matches, "leds") {
+        Some(leds_file) => match LedResponse::load(leds_file) {
+            Ok(response) => response,
+            Err(error) => fail!(error)
+        },
+        None => LedResponse::new()
+    };
+    
+    // Exporting needs no window, or even a display to open one on
+    match opt_maybe_str(&matches, "export") {
+        Some(output) => {
+            let scale = match opt_maybe_str(&matches, "export-scale") {
+                Some(scale) => match float::from_str(scale) {
+                    Some(scale) if scale > 0.0 => scale as f32,
+                    _ => fail!("--export-scale should be a positive number")
+                },
+                None => 1.0
+            };
+            let meshes = build_scene(&matches, rods, &response);
+            match export::export(output, meshes, scale / layout::SCENE_SCALE) {
+                Ok(()) => printfln!("Exported %u objects to %s", meshes.len(), output),
+                Err(error) => fail!(error)
+            }
+            return;
+        },
+        None => {}
+    }
+    
     do glfw::spawn {
-        let rods_file = match os::args() {
-            [_, path] => path,
-            _ => ~"default-rods.json"
+        let (width, height) = match opt_maybe_str(&matches, "size") {
+            Some(size) => match parse_size(size) {
+                Some(size) => size,
+                None => fail!("--size should look like 800x600")
+            },
+            None => (SCREEN_WIDTH, SCREEN_HEIGHT)
+        };
+        
+        let recording = match opt_maybe_str(&matches, "record") {
+            Some(output) => {
+                let events = match opt_maybe_str(&matches, "show") {
+                    Some(show_file) => match show::load_show(show_file) {
+                        Ok(events) => events,
+                        Err(error) => fail!(error)
+                    },
+                    None => fail!("--record needs a --show to play")
+                };
+                let fps = match opt_maybe_str(&matches, "fps") {
+                    Some(fps) => match uint::from_str_radix(fps, 10) {
+                        Some(fps) if fps > 0 => fps,
+                        _ => fail!("--fps should be a positive whole number")
+                    },
+                    None => 30
+                };
+                let duration = match opt_maybe_str(&matches, "duration") {
+                    Some(duration) => match float::from_str(duration) {
+                        Some(duration) if duration > 0.0 => Some(duration),
+                        _ => fail!("--duration should be a positive number of seconds")
+                    },
+                    None => None
+                };
+                Some((record::Settings { output: output, fps: fps, duration: duration }, events))
+            },
+            None => None
         };
 
-        let rods_reader = match io::file_reader(&Path(rods_file)) {
-            Ok(reader) => reader,
+        let mut effect = match opt_maybe_str(&matches, "effect") {
+            Some(_) if recording.is_some() => fail!("--effect can't be recorded; write it as a --show"),
+            Some(name) => match effects::by_name(name) {
+                Some(effect) => Some(effect),
+                None => fail!(fmt!("--effect should be one of %s", effects::NAMES.connect(", ")))
+            },
+            None => None
+        };
+        let positions: ~[Position] = rods.iter().transform(|rod| Position::new(rod.x, rod.y, rod.height)).collect();
+        let mut colors = vec::from_elem(rods.len(), (0u8, 0u8, 0u8));
+        
+        let view_layout = match opt_maybe_str(&matches, "view") {
+            Some(name) => match ViewLayout::from_name(name) {
+                Some(layout) => layout,
+                None => fail!("--view should be perspective, plan, elevation or split")
+            },
+            None => Single(Perspective)
+        };
+        
+        let mut bookmarks = match Bookmarks::load(BOOKMARKS_FILE) {
+            Ok(bookmarks) => bookmarks,
             Err(error) => fail!(error)
         };
+        let mut hud = Hud::new();
+        let mut selected: Option<uint> = None;
+        let mut next_path = 0u;
+        let mut flythrough: Option<(Flythrough, float)> = None;
         
-        let rods_specs = match json::from_reader(rods_reader) {
-            Ok(json) => match json {
-                json::List(list) => list,
-                _ => fail!("Invalid JSON")
+        let flythrough_path = match opt_maybe_str(&matches, "path") {
+            Some(name) => match bookmarks.find_path(name) {
+                Some(path) => match Flythrough::new(path, &bookmarks) {
+                    Ok(f) => Some(f),
+                    Err(error) => fail!(error)
+                },
+                None => fail!(fmt!("No camera path named \"%s\" in %s", name, BOOKMARKS_FILE))
             },
-            Err(_) => fail!("Invalid JSON")
+            None => None
         };
         
-        let ports = start_server(rods_specs.len() as u8);
+        // Recordings are driven entirely by the show, and effects by
+        // themselves, so there's no server
+        let (mut ports, mut clients) = if recording.is_some() || effect.is_some() {
+            (None, None)
+        } else {
+            let (ports, clients) = server::start_server(rods.len() as u8, opt_maybe_str(&matches, "capture"));
+            (Some(ports), Some(clients))
+        };
         
         glfw::window_hint::context_version(3, 2);
         glfw::window_hint::opengl_profile(glfw::OPENGL_CORE_PROFILE);
         glfw::window_hint::opengl_forward_compat(true);
         glfw::window_hint::samples(8);
+        glfw::window_hint::visible(recording.is_none());
         
-        let window = ~glfw::Window::create(SCREEN_WIDTH, SCREEN_HEIGHT, "Light Sculpture Simulator", glfw::Windowed).unwrap();
+        let window = ~glfw::Window::create(width, height, "Light Sculpture Simulator", glfw::Windowed).unwrap();
         
         window.make_context_current();
         
         let vao = check!(es::gen_vertex_arrays(1)[0]);
         check!(es::bind_vertex_array(vao));
 
-        let mut camera = Camera::new(window, "shaders/everything");
-        let base = Mesh::gen_base();
-        camera.add_mesh(base);
-        
-        for rods_specs.consume_iter().zip(ports.consume_iter()).advance |(json, port)| {
-            match json {
-                json::Object(obj) => {
-                    let (x, y) = if obj.contains_key_equiv(&("x")) && obj.contains_key_equiv(&("y")) {
-                        (match obj.find_equiv(&("x")) {
-                            Some(val) => match *val {
-                                json::Number(num) => num,
-                                _ => fail!("Invalid JSON")
-                            },
-                            None => fail!("Invalid JSON")
-                        }, match obj.find_equiv(&("y")) {
-                            Some(val) => match *val {
-                                json::Number(num) => num,
-                                _ => fail!("Invalid JSON")
-                            },
-                            None => fail!("Invalid JSON")
-                        })
-                    } else if obj.contains_key_equiv(&("angle")) && obj.contains_key_equiv(&("radius")) {
-                        let angle = match obj.find_equiv(&("angle")) {
-                            Some(val) => match *val {
-                                json::Number(num) => num,
-                                _ => fail!("Invalid JSON")
-                            },
-                            None => fail!("Invalid JSON")
-                        };
-                        let radius = match obj.find_equiv(&("radius")) {
-                            Some(val) => match *val {
-                                json::Number(num) => num,
-                                _ => fail!("Invalid JSON")
-                            },
-                            None => fail!("Invalid JSON")
-                        };
-                        (radius * angle.cos(), radius * angle.sin())
-                    } else {
-                        fail!("Invalid JSON")
-                    };
-                    let height = match obj.find_equiv(&("height")) {
-                        Some(val) => match *val {
-                            json::Number(num) => num,
-                            _ => fail!("Invalid JSON")
-                        },
-                        None => fail!("Invalid JSON")
-                    };
-                    camera.add_mesh(Mesh::better_rod((x / 10.0) as f32,
-                                                     (y / 10.0) as f32,
-                                                     (height / 10.0) as f32,
-                                                     port))
-                },
-                _ => fail!("Invalid JSON")
+        let shader_dir = opt_maybe_str(&matches, "shaders");
+        let (_, default_shader) = SHADER_PRESETS[0];
+        let mut camera = Camera::new(window, &shader_source(&shader_dir, default_shader));
+        // Rods come in layout order, the same order as the ports
+        for build_scene(&matches, rods, &response).consume_iter().advance |mesh| {
+            let mut mesh = mesh;
+            if mesh.rod.is_some() {
+                match ports {
+                    Some(ref mut ports) => mesh.listen(ports.shift()),
+                    None => {}
+                }
             }
+            camera.add_mesh(mesh);
         }
         
-        camera.look_at(Vec3::new(0.0f32, 1.0, 0.0), Vec3::new(0.0, -2.0, -2.0), Vec3::new(0.0, 0.0, 1.0));
-        camera.perspective(3.14159 / 4.0f32, 0.1, 10.0);
+        camera.set_layout(view_layout);
         
         es::enable(es::BLEND);
         es::enable(es::DEPTH_TEST);
         es::blend_func(es::SRC_ALPHA, es::ONE_MINUS_SRC_ALPHA);
+        
+        match recording {
+            Some((ref settings, ref events)) => {
+                let flythrough = match flythrough_path { Some(ref f) => Some(f), None => None };
+                match record::record(&mut camera, *events, flythrough, settings) {
+                    Ok(frames) => printfln!("Recorded %u frames to %s", frames, settings.output),
+                    Err(error) => fail!(error)
+                }
+                return;
+            },
+            None => {}
+        }
 
+        let effect_start = glfw::get_time();
         while !camera.should_close() {
             glfw::poll_events();
+            camera.update_size();
+            camera.reload_shaders();
+            
+            if camera.was_key_pressed(glfw::KEY_F11) {
+                camera.toggle_cover_monitor();
+            }
+            if camera.was_key_pressed(glfw::KEY_H) {
+                hud.toggle();
+            }
+            if camera.was_key_pressed(glfw::KEY_V) {
+                let layout = camera.layout.next();
+                camera.set_layout(layout);
+            }
+            for SHADER_PRESETS.iter().enumerate().advance |(i, &(name, file_name))| {
+                if camera.was_key_pressed(glfw::KEY_F1 + i as c_int) {
+                    match camera.set_shader(&shader_source(&shader_dir, file_name)) {
+                        Ok(()) => printfln!("Using the %s shader", name),
+                        Err(error) => error!(error.to_str())
+                    }
+                }
+            }
+            
+            if camera.was_clicked() {
+                let (x, y) = camera.cursor_pos();
+                selected = picking::pick_rod(&camera.pick_ray(x, y), rods);
+                camera.select_rod(selected);
+            }
+            if camera.was_key_pressed(glfw::KEY_ESCAPE) {
+                selected = None;
+                camera.select_rod(selected);
+            }
+            
+            // Number keys recall bookmarks; with shift held they save one
+            let shift = camera.is_key_down(glfw::KEY_LEFT_SHIFT) || camera.is_key_down(glfw::KEY_RIGHT_SHIFT);
+            for uint::range(1, 10) |slot| {
+                if camera.was_key_pressed(glfw::KEY_0 + slot as c_int) {
+                    if shift {
+                        bookmarks.set_slot(slot, camera.pose());
+                        match bookmarks.save() {
+                            Ok(()) => printfln!("Saved view %u to %s", slot, BOOKMARKS_FILE),
+                            Err(error) => error!(error)
+                        }
+                    } else {
+                        match bookmarks.find_slot(slot) {
+                            Some(bookmark) => { camera.set_pose(&bookmark.pose); flythrough = None; },
+                            None => {}
+                        }
+                    }
+                }
+            }
+            
+            // P plays each flythrough path in turn, or stops the one playing
+            if camera.was_key_pressed(glfw::KEY_P) {
+                if flythrough.is_some() {
+                    flythrough = None;
+                } else if bookmarks.paths.len() > 0 {
+                    let path = &bookmarks.paths[next_path % bookmarks.paths.len()];
+                    next_path += 1;
+                    match Flythrough::new(path, &bookmarks) {
+                        Ok(f) => { printfln!("Playing %s", path.name); flythrough = Some((f, glfw::get_time())); },
+                        Err(error) => error!(error)
+                    }
+                }
+            }
+            
+            let finished = match flythrough {
+                Some((ref f, start)) => match f.pose_at((glfw::get_time() - start) as f32) {
+                    Some(pose) => { camera.set_pose(&pose); false },
+                    None => true
+                },
+                None => false
+            };
+            if finished {
+                flythrough = None;
+            }
             
             if camera.is_key_down(glfw::KEY_Q) {
                 camera.translate(Vec3::new(0.0, 0.05, 0.0));
@@ -163,61 +446,37 @@ fn main() {
                 camera.rotate(0.0, 3.14159 / -40.0, 0.0);
             }
             
-            es::clear_color(0.9, 0.9, 0.9, 1.0);
-            es::clear(es::COLOR_BUFFER_BIT | es::DEPTH_BUFFER_BIT);
+            match clients {
+                Some(ref mut clients) => clients.update(),
+                None => {}
+            }
+            match effect {
+                Some(ref mut effect) => {
+                    effect.render(glfw::get_time() - effect_start, positions, colors);
+                    for colors.iter().enumerate().advance |(i, &(r, g, b))| {
+                        match camera.rod_mut(i) {
+                            Some(rod) if rod.rgb != (r, g, b) => rod.set_rgb(r, g, b),
+                            _ => {}
+                        }
+                    }
+                },
+                None => {}
+            }
             
-            camera.draw();
+            camera.render();
+            hud.draw(&mut camera, rods, match clients { Some(ref clients) => Some(clients), None => None }, selected);
+            camera.present();
         }
     }
 }
 
-fn start_server(num_leds: u8) -> ~[comm::Port<(f32, f32, f32)>] {
-    let mut ports: ~[comm::Port<(f32, f32, f32)>] = ~[];
-    let mut chans: ~[comm::SharedChan<(f32, f32, f32)>] = ~[];
-    for (num_leds as uint).times {
-        let (port, chan) = comm::stream();
-        ports.push(port);
-        chans.push(comm::SharedChan::new(chan));
+fn parse_size(size: &str) -> Option<(uint, uint)> {
+    let parts: ~[&str] = size.split_iter('x').collect();
+    if parts.len() != 2 {
+        return None;
     }
-    let chans = chans;
-    
-    let iotask = uv_global_loop::get();
-    let localhost = net::ip::v4::parse_addr("127.0.0.1");
-    
-    do task::spawn {
-        
-        println("um");
-        let cloned_chans = chans.clone();
-        do net::tcp::listen(localhost, 7654, 100, &iotask, |_| ()) |new_conn, _| {
-            let my_chans = cloned_chans.clone();
-            do task::spawn {
-                let accept_result = net::tcp::accept(new_conn);
-                let sock = match accept_result {
-                    Ok(val) => val,
-                    Err(_) => {
-                        fail!("Socket error")
-                    }
-                };
-                printfln!("Connection from %s", net::ip::format_addr(&sock.get_peer_addr()));
-                let sock_buf = net::tcp::socket_buf(sock);
-                loop {
-                    let leds = sock_buf.read_byte();
-                    if leds < 0 {
-                        break;
-                    }
-                    for uint::range(0, leds as uint) |_| {
-                        let bytes = sock_buf.read_bytes(4);
-                        let led = bytes[0];
-                        if led < num_leds {
-                            let r: f32 = (bytes[1] as f32) / 255.0f32;
-                            let g: f32 = (bytes[2] as f32) / 255.0f32;
-                            let b: f32 = (bytes[3] as f32) / 255.0f32;
-                            my_chans[led].send((r, g, b));
-                        }
-                    }
-                }
-            }
-        };
+    match (uint::from_str_radix(parts[0], 10), uint::from_str_radix(parts[1], 10)) {
+        (Some(width), Some(height)) if width > 0 && height > 0 => Some((width, height)),
+        _ => None
     }
-    return ports;
-}
\ No newline at end of file
+}